@@ -13,8 +13,11 @@
 /*!40111 SET @OLD_SQL_NOTES=@@SQL_NOTES, SQL_NOTES=0 */;
 
 
+-- Only the database has to exist, the game creates and upgrades the score table
+-- with the versioned migrations in /migrations on startup.
+
 -- Dumping database structure for gildaga
-CREATE DATABASE IF NOT EXISTS `gildaga` /*!40100 DEFAULT CHARACTER SET latin1 */;
+CREATE DATABASE IF NOT EXISTS `gildaga` /*!40100 DEFAULT CHARACTER SET utf8mb4 */;
 USE `gildaga`;
 
 -- Dumping structure for table gildaga.score
@@ -23,7 +26,7 @@ CREATE TABLE IF NOT EXISTS `score` (
   `Username` varchar(50) NOT NULL DEFAULT 'blaap',
   `Score` int(11) NOT NULL DEFAULT 0,
   PRIMARY KEY (`UserID`)
-) ENGINE=InnoDB AUTO_INCREMENT=9 DEFAULT CHARSET=latin1;
+) ENGINE=InnoDB AUTO_INCREMENT=9 DEFAULT CHARSET=utf8mb4;
 
 -- Dumping data for table gildaga.score: ~3 rows (approximately)
 /*!40000 ALTER TABLE `score` DISABLE KEYS */;
@@ -0,0 +1,9 @@
+-- the score table as it was shipped in database/script.sql, moved to utf8mb4 so any name fits
+CREATE TABLE IF NOT EXISTS `score` (
+  `UserID` int(11) NOT NULL AUTO_INCREMENT,
+  `Username` varchar(50) NOT NULL DEFAULT 'blaap',
+  `Score` int(11) NOT NULL DEFAULT 0,
+  PRIMARY KEY (`UserID`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+ALTER TABLE `score` CONVERT TO CHARACTER SET utf8mb4 COLLATE utf8mb4_unicode_ci;
@@ -0,0 +1,6 @@
+-- when and how a score was reached
+ALTER TABLE `score`
+  ADD COLUMN `CreatedAt` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  ADD COLUMN `GameMode` varchar(20) NOT NULL DEFAULT 'single',
+  ADD COLUMN `WaveReached` int(11) NOT NULL DEFAULT 0,
+  ADD COLUMN `GameVersion` varchar(20) NOT NULL DEFAULT '';
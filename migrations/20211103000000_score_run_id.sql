@@ -0,0 +1,4 @@
+-- one score per run, older rows have no run id
+ALTER TABLE `score`
+  ADD COLUMN `RunID` varchar(32) NULL DEFAULT NULL,
+  ADD UNIQUE INDEX `RunID` (`RunID`);
@@ -0,0 +1,21 @@
+-- what happened during the run of a score, older rows have none
+CREATE TABLE IF NOT EXISTS `score_stats` (
+  `ScoreID` int(11) NOT NULL,
+  `ShotsFired` int(11) NOT NULL DEFAULT 0,
+  `Hits` int(11) NOT NULL DEFAULT 0,
+  `Deaths` int(11) NOT NULL DEFAULT 0,
+  `WavesCleared` int(11) NOT NULL DEFAULT 0,
+  `TimeSurvived` float NOT NULL DEFAULT 0,
+  `HighestCombo` int(11) NOT NULL DEFAULT 0,
+  PRIMARY KEY (`ScoreID`),
+  FOREIGN KEY (`ScoreID`) REFERENCES `score` (`UserID`) ON DELETE CASCADE
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+-- enemies killed during the run, one row per enemy type
+CREATE TABLE IF NOT EXISTS `score_kills` (
+  `ScoreID` int(11) NOT NULL,
+  `EnemyType` varchar(50) NOT NULL,
+  `Kills` int(11) NOT NULL DEFAULT 0,
+  PRIMARY KEY (`ScoreID`, `EnemyType`),
+  FOREIGN KEY (`ScoreID`) REFERENCES `score` (`UserID`) ON DELETE CASCADE
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
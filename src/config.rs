@@ -0,0 +1,79 @@
+use std::fs;
+use std::path::PathBuf;
+use bevy::log::warn;
+use serde::Deserialize;
+use crate::store::ScoreBackend;
+use crate::ui::NameEntryMode;
+
+const CONFIG_FILE: &str = "gildaga.ron";
+
+//settings read from gildaga.ron, the GILDAGA_* environment variables win over the file
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Config{
+    pub score_store: ScoreBackend,
+    pub database_url: String,
+    pub name_entry: NameEntryMode,
+    //asset path of the level to play
+    pub level: String,
+}
+
+impl Default for Config{
+    fn default() -> Self {
+        Config{
+            score_store: ScoreBackend::MySql,
+            database_url: "mysql://localhost/gildaga".to_string(),
+            name_entry: NameEntryMode::Text,
+            level: "levels/default.level.ron".to_string(),
+        }
+    }
+}
+
+impl Config{
+    pub fn load() -> Self {
+        let mut config = Config::read_file().unwrap_or_default();
+
+        if let Ok(store) = std::env::var("GILDAGA_SCORE_STORE"){
+            match store.as_str(){
+                "mysql" => config.score_store = ScoreBackend::MySql,
+                "local" => config.score_store = ScoreBackend::Local,
+                _ => warn!("unknown GILDAGA_SCORE_STORE {}, expected mysql or local", store),
+            }
+        }
+        if let Ok(url) = std::env::var("GILDAGA_DATABASE_URL"){
+            config.database_url = url;
+        }
+        config
+    }
+
+    //the working directory first so a checkout can carry its own config
+    fn path() -> Option<PathBuf> {
+        let local = PathBuf::from(CONFIG_FILE);
+        if local.exists(){
+            return Some(local);
+        }
+        let user = dirs::config_dir()?.join("gildaga").join(CONFIG_FILE);
+        if user.exists(){
+            return Some(user);
+        }
+        None
+    }
+
+    fn read_file() -> Option<Self> {
+        let path = Config::path()?;
+        let content = match fs::read_to_string(&path){
+            Ok(content) => content,
+            Err(e) => {
+                warn!("could not read {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        match ron::de::from_str(&content){
+            Ok(config) => Some(config),
+            Err(e) => {
+                warn!("could not parse {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+use bevy::tasks::{IoTaskPool, Task};
+use futures::FutureExt;
+use std::sync::Arc;
+use crate::config::Config;
+use crate::store::{NewScore, ScoreRow, ScoreStore};
+
+pub struct DatabasePlugin;
+
+impl Plugin for DatabasePlugin{
+    fn build(&self, app: &mut AppBuilder) {
+        let config = app.world().get_resource::<Config>().expect("Config has to be inserted before the DatabasePlugin");
+        let scores = Scores(config.score_store.open(&config.database_url));
+
+        app
+            .insert_resource(scores)
+            .add_startup_system(migrate.system())
+            .add_system(migrate_poll.system())
+            .add_event::<SaveScoreRequest>()
+            .add_event::<ScoreSaved>()
+            .add_event::<LoadHighScoresRequest>()
+            .add_event::<HighScoresLoaded>()
+            .add_system(save_score_request.system())
+            .add_system(save_score_poll.system())
+            .add_system(load_highscores_request.system())
+            .add_system(load_highscores_poll.system());
+    }
+}
+
+struct Scores(Arc<dyn ScoreStore>);
+
+pub struct SaveScoreRequest(pub NewScore);
+pub struct ScoreSaved{
+    pub run_id: String,
+    //id of the inserted row or the reason it failed
+    pub result: Result<u64, String>,
+}
+
+pub struct LoadHighScoresRequest(pub u32);
+pub struct HighScoresLoaded(pub Result<Vec<ScoreRow>, String>);
+
+struct MigrateTask(Task<Result<(), String>>);
+struct SaveScoreTask(String, Task<Result<u64, String>>);
+struct LoadHighScoresTask(Task<Result<Vec<ScoreRow>, String>>);
+
+fn migrate(
+    mut commands: Commands,
+    task_pool: Res<IoTaskPool>,
+    scores: Res<Scores>,
+){
+    let task = task_pool.spawn(scores.0.migrate());
+    commands.spawn().insert(MigrateTask(task));
+}
+
+fn migrate_poll(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut MigrateTask)>,
+){
+    for (entity, mut task) in query.iter_mut(){
+        if let Some(result) = (&mut task.0).now_or_never(){
+            if let Err(e) = result{
+                error!("could not migrate the score store: {}", e);
+            }
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn save_score_request(
+    mut commands: Commands,
+    task_pool: Res<IoTaskPool>,
+    scores: Res<Scores>,
+    mut requests: EventReader<SaveScoreRequest>,
+){
+    for SaveScoreRequest(score) in requests.iter(){
+        let task = task_pool.spawn(scores.0.save(score.clone()));
+        commands.spawn().insert(SaveScoreTask(score.run_id.clone(), task));
+    }
+}
+
+fn save_score_poll(
+    mut commands: Commands,
+    mut saved: EventWriter<ScoreSaved>,
+    mut query: Query<(Entity, &mut SaveScoreTask)>,
+){
+    for (entity, mut task) in query.iter_mut(){
+        if let Some(result) = (&mut task.1).now_or_never(){
+            saved.send(ScoreSaved{
+                run_id: task.0.clone(),
+                result,
+            });
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn load_highscores_request(
+    mut commands: Commands,
+    task_pool: Res<IoTaskPool>,
+    scores: Res<Scores>,
+    mut requests: EventReader<LoadHighScoresRequest>,
+){
+    for request in requests.iter(){
+        let task = task_pool.spawn(scores.0.load_top(request.0));
+        commands.spawn().insert(LoadHighScoresTask(task));
+    }
+}
+
+fn load_highscores_poll(
+    mut commands: Commands,
+    mut loaded: EventWriter<HighScoresLoaded>,
+    mut query: Query<(Entity, &mut LoadHighScoresTask)>,
+){
+    for (entity, mut task) in query.iter_mut(){
+        if let Some(result) = (&mut task.0).now_or_never(){
+            loaded.send(HighScoresLoaded(result));
+            commands.entity(entity).despawn();
+        }
+    }
+}
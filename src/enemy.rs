@@ -1,5 +1,9 @@
 use bevy::{core::FixedTimestep, prelude::*};
-use crate::{WinSize, Materials, ActiveEnemies, Enemy, SCALE, Laser, FromEnemy, Speed, TIME_STEP, MAX_ENEMIES, MAX_FORMATION_MEMBERS, PauseState, GameState, LaserSpeed};
+use crate::{WinSize, Materials, ActiveEnemies, Enemy, SCALE, Laser, FromEnemy, Speed, TIME_STEP, PauseState, GameState, LaserSpeed, Wave, WaveBanner, HitPoints, Player, Slot, Velocity, Diving};
+use crate::level::{CurrentLevel, Level, WaveDef, EnemyType, Movement, Weapon};
+use crate::path::FollowPath;
+use crate::tractor::Beaming;
+use crate::state::COLLISIONS;
 use rand::{thread_rng, Rng};
 use std::f32::consts::PI;
 use bevy_inspector_egui::{Inspectable, InspectorPlugin, WorldInspectorPlugin};
@@ -7,77 +11,117 @@ use bevy_inspector_egui::widgets::{InspectorQuerySingle, InspectorQuery};
 
 pub struct EnemyPlugin;
 
-#[derive(Inspectable, Default, Clone)]
-struct Formation{
-    start: (f32, f32),
-    radius: (f32, f32),
-    offset: (f32, f32),
-    angle: f32,
-    group_id: u32,
-}
-
-#[derive(Inspectable, Default)]
-struct FormationMaker{
-    group_seq: u32,
-    current_formation: Option<Formation>,
-    current_formation_members: u32,
-}
-
-impl FormationMaker{
-    fn make(&mut self, win_size: &WinSize) -> Formation {
-        match(
-            &self.current_formation,
-            self.current_formation_members >= MAX_FORMATION_MEMBERS
-        ){
-            //if first formation or previous formation full
-            (None, _) | (_, true) => {
-                let mut rng = thread_rng();
-                //compute start x/y
-                let h_span = win_size.h / 2. - 100.;
-                let w_span = win_size.w / 4.;
-                let x = if rng.gen::<bool>() {
-                    win_size.w
-                } else {
-                    -win_size.w
-                };
-
-                let y = rng.gen_range(-h_span..h_span) as f32;
-                let start = (x, y);
-
-                let offset = (rng.gen_range(-w_span..w_span), rng.gen_range(0.0..h_span));
-                let radius = (rng.gen_range(80.0..150.), 100.);
-                let angle: f32 = (y - offset.0).atan2(x - offset.1);
-
-                self.group_seq += 1;
-                let group_id = self.group_seq;
-                let formation = Formation{
-                    start,
-                    offset,
-                    radius,
-                    angle,
-                    group_id,
-                };
-
-                self.current_formation = Some(formation.clone());
-                self.current_formation_members = 1;
-                formation
-            }
-            //if still within the formation count
-            (Some(tmpl), false) => {
-                self.current_formation_members += 1;
-                tmpl.clone()
-            }
+//the grid sways side to side, one full sway every 2 * PI / GRID_BREATH_SPEED seconds
+const GRID_SLOT_WIDTH: f32 = 50.;
+const GRID_SLOT_HEIGHT: f32 = 45.;
+const GRID_TOP_MARGIN: f32 = 120.;
+const GRID_BREATH_SWAY: f32 = 40.;
+const GRID_BREATH_SPEED: f32 = 0.8;
+//seconds between two attackers leaving the grid
+const ATTACK_INTERVAL: f32 = 2.5;
+
+//the formation the enemies fly in to, every enemy has a Slot in it
+struct FormationGrid{
+    phase: f32,
+    attack: Timer,
+}
+
+impl Default for FormationGrid{
+    fn default() -> Self {
+        FormationGrid{
+            phase: 0.,
+            attack: Timer::from_seconds(ATTACK_INTERVAL, true),
+        }
+    }
+}
+
+impl FormationGrid{
+    //where a slot currently is on screen
+    fn home(&self, slot: &Slot, win_size: &WinSize) -> Vec2 {
+        Vec2::new(
+            slot.x * GRID_SLOT_WIDTH + GRID_BREATH_SWAY * self.phase.sin(),
+            win_size.h / 2. - GRID_TOP_MARGIN - slot.row as f32 * GRID_SLOT_HEIGHT,
+        )
+    }
+}
+
+//enemies of a group fly in one after the other along the same entry path
+#[derive(Default)]
+struct EntryMaker{
+    //1. enters from the right, -1. from the left
+    side: f32,
+    members: u32,
+    //a group only holds one enemy type
+    current_type: String,
+}
+
+impl EntryMaker{
+    fn side(&mut self, wave_def: &WaveDef, enemy_type: &str) -> f32 {
+        if self.side == 0. || self.members >= wave_def.formation_members || self.current_type != enemy_type {
+            self.side = if thread_rng().gen::<bool>() { 1. } else { -1. };
+            self.members = 0;
+            self.current_type = enemy_type.to_string();
         }
+        self.members += 1;
+        self.side
+    }
+}
+
+//swoop in from the side across the lower screen, then curve up to the slot
+fn entry_path(side: f32, home: Vec2, win_size: &WinSize) -> FollowPath {
+    FollowPath::new(vec![
+        Vec2::new(side * (win_size.w / 2. + 50.), win_size.h / 4.),
+        Vec2::new(side * win_size.w / 4., 0.),
+        Vec2::new(0., -win_size.h / 8.),
+        Vec2::new(-side * win_size.w / 8., win_size.h / 16.),
+        home,
+    ])
+}
+
+//loop out of the formation, swoop down at the target and either exit at the bottom
+//or curve back up to the slot the dive started from
+fn dive_path(start: Vec2, target: Vec2, win_size: &WinSize, exit_bottom: bool) -> FollowPath {
+    //loop away from the screen center
+    let dir = if start.x > 0. { 1. } else { -1. };
+    let mut points = vec![
+        start,
+        start + Vec2::new(dir * 40., 50.),
+        start + Vec2::new(dir * 100., 20.),
+        Vec2::new((start.x + target.x) / 2. + dir * 60., (start.y + target.y) / 2.),
+        target,
+    ];
+    if exit_bottom {
+        points.push(Vec2::new(target.x - dir * 60., -win_size.h / 2. - 60.));
+    } else {
+        points.push(Vec2::new(target.x - dir * 120., target.y + 20.));
+        points.push(Vec2::new((start.x + target.x) / 2. - dir * 80., (start.y + target.y) / 2.));
+        points.push(start);
     }
+    FollowPath::new(points)
 }
 
 impl Plugin for EnemyPlugin{
     fn build(&self, app: &mut bevy::prelude::AppBuilder){
         app
-            .insert_resource(FormationMaker::default())
-            .add_system(enemy_laser_movement.system())
-            .add_system(enemy_movement.system())
-            .add_system(enemy_fire.system())
+            .insert_resource(FormationGrid::default())
+            .insert_resource(EntryMaker::default())
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(enemy_laser_movement.system())
+                    .with_system(grid_breathe.system())
+                    .with_system(enemy_movement.system())
+                    .with_system(enemy_attack.system().before(COLLISIONS))
+                    .with_system(enemy_fire.system().before(COLLISIONS))
+                    .with_system(wave_progress.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver)
+                    .with_system(wave_banner_hide.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::HighScoreEntry)
+                    .with_system(enemy_reset.system())
+            )
             .add_system_set(
             SystemSet::new()
                 .with_run_criteria(FixedTimestep::step(1.0))
@@ -89,116 +133,307 @@ impl Plugin for EnemyPlugin{
 fn enemy_spawn(
     mut commands: Commands,
     mut active_enemies: ResMut<ActiveEnemies>,
-    mut formation_maker: ResMut<FormationMaker>,
-    game_state: Res<GameState>,
+    mut entry_maker: ResMut<EntryMaker>,
+    mut wave: ResMut<Wave>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    grid: Res<FormationGrid>,
+    game_state: Res<State<GameState>>,
     win_size: Res<WinSize>,
-    materials: Res<Materials>
+    asset_server: Res<AssetServer>,
+    levels: Res<Assets<Level>>,
+    current_level: Res<CurrentLevel>,
 ) {
+    let level = match current_level.get(&levels, &asset_server){
+        Some(level) => level,
+        None => return,
+    };
+    let wave_def = level.wave(wave.number);
 
-    if active_enemies.0 < MAX_ENEMIES && game_state.0 != "gameover".to_string(){
+    if !wave.to_spawn.is_empty() && wave.banner.finished() && active_enemies.0 < wave_def.max_active && *game_state.current() == GameState::Playing{
 
-        let formation = formation_maker.make(&win_size);
-        let (x, y) = formation.start;
+        let (type_name, slot) = wave.to_spawn.remove(0);
+        let enemy_type = match level.enemy_types.get(&type_name){
+            Some(enemy_type) => enemy_type.clone(),
+            None => {
+                warn!("unknown enemy type {} in wave {}", type_name, wave.number);
+                return;
+            }
+        };
+
+        let side = entry_maker.side(&wave_def, &type_name);
+        let path = entry_path(side, grid.home(&slot, &win_size), &win_size);
+        let (r, g, b) = enemy_type.tint;
+        let material = materials.add(ColorMaterial::modulated_texture(
+            asset_server.load(enemy_type.sprite.as_str()),
+            Color::rgb(r, g, b),
+        ));
+        let scale = SCALE * enemy_type.scale;
 
         commands
             .spawn_bundle(SpriteBundle{
-                material: materials.enemy.clone(),
+                material,
                 transform: Transform{
-                    translation: Vec3::new(x, y, 10.0),
-                    scale: Vec3::new(SCALE, SCALE, 0.5),
+                    translation: Vec3::new(side * (win_size.w / 2. + 50.), win_size.h / 4., 10.0),
+                    scale: Vec3::new(scale, scale, 0.5),
                     ..Default::default()
                 },
                 ..Default::default()
             })
             .insert(Enemy)
-            .insert(Speed::default())
-            .insert(LaserSpeed::default())
-            .insert(Timer::from_seconds(0.9, true))
-            .insert(formation)
-            .insert(if game_state.0 == "active" {PauseState::default()} else {PauseState(true)});
+            .insert(HitPoints(enemy_type.hit_points))
+            .insert(Speed{v: wave_def.speed * enemy_type.speed})
+            .insert(LaserSpeed{v: wave_def.laser_speed})
+            .insert(Timer::from_seconds(wave_def.fire_interval, true))
+            .insert(slot)
+            .insert(path)
+            .insert(enemy_type)
+            .insert(PauseState::default());
 
         active_enemies.0 += 1;
     }
 }
 
-fn enemy_movement(
+fn wave_progress(
     time: Res<Time>,
-    mut query: Query<(&mut Transform, &PauseState, &Speed, &mut Formation), With<Enemy>>
+    active_enemies: Res<ActiveEnemies>,
+    asset_server: Res<AssetServer>,
+    levels: Res<Assets<Level>>,
+    current_level: Res<CurrentLevel>,
+    mut wave: ResMut<Wave>,
+    mut entry_maker: ResMut<EntryMaker>,
+    mut banner_query: Query<(&mut Text, &mut Visible), With<WaveBanner>>,
+){
+    //wave cleared, on to the next one
+    if wave.to_spawn.is_empty() && active_enemies.0 == 0 {
+        let next_wave = match current_level.get(&levels, &asset_server){
+            Some(level) => level.wave(wave.number + 1),
+            //level file still loading
+            None => return,
+        };
+        wave.number += 1;
+        wave.to_spawn = next_wave.spawn_queue();
+        wave.banner.reset();
+        *entry_maker = EntryMaker::default();
+        for (mut text, _) in banner_query.iter_mut(){
+            text.sections[0].value = format!("Wave {}", wave.number);
+        }
+    }
+
+    wave.banner.tick(time.delta());
+    for (_, mut visible) in banner_query.iter_mut(){
+        visible.is_visible = !wave.banner.finished();
+    }
+}
+
+fn wave_banner_hide(
+    mut banner_query: Query<&mut Visible, With<WaveBanner>>,
+){
+    for mut visible in banner_query.iter_mut(){
+        visible.is_visible = false;
+    }
+}
+
+fn enemy_reset(
+    mut commands: Commands,
+    mut active_enemies: ResMut<ActiveEnemies>,
+    mut grid: ResMut<FormationGrid>,
+    mut entry_maker: ResMut<EntryMaker>,
+    mut wave: ResMut<Wave>,
+    query: Query<Entity, Or<(With<Enemy>, With<FromEnemy>)>>,
+){
+    for entity in query.iter(){
+        commands.entity(entity).despawn();
+    }
+    active_enemies.0 = 0;
+    *grid = FormationGrid::default();
+    *entry_maker = EntryMaker::default();
+    *wave = Wave::default();
+}
+
+fn grid_breathe(
+    time: Res<Time>,
+    mut grid: ResMut<FormationGrid>,
+){
+    grid.phase += GRID_BREATH_SPEED * time.delta_seconds();
+}
+
+//enemies not on a path fly back to their slot and stay there
+fn enemy_movement(
+    mut commands: Commands,
+    win_size: Res<WinSize>,
+    grid: Res<FormationGrid>,
+    mut query: Query<(Entity, &mut Transform, &PauseState, &Speed, &Slot, Option<&FollowPath>), (With<Enemy>, Without<Beaming>)>,
 ){
     //foreach enemy
-    for (mut tf, pause, speed, mut formation) in query.iter_mut(){
+    for (entity, mut tf, pause, speed, slot, path) in query.iter_mut(){
 
         if !pause.0 {
+            //the path moves it while entering or diving
+            if let Some(path) = path {
+                if path.finished(){
+                    commands.entity(entity)
+                        .remove::<FollowPath>()
+                        .remove::<Diving>();
+                    //left at the bottom, come back in from the top
+                    if tf.translation.y < -win_size.h / 2. {
+                        tf.translation.y = win_size.h / 2. + 50.;
+                    }
+                }
+                continue;
+            }
+
             let max_distance = TIME_STEP * speed.v;
-            let x_org = tf.translation.x;
-            let y_org = tf.translation.y;
-
-            //ellipse
-            let (x_offset, y_offset) = formation.offset;
-            let (x_radius, y_radius) = formation.radius;
-
-            // Compute the next angle
-            let dir = if formation.start.0 > 0. { 1. } else { -1. };
-            let angle = formation.angle + dir * speed.v * TIME_STEP / (x_radius.min(y_radius) * PI / 2.);
-
-            // Calculate destination
-            let x_dst = x_radius * angle.cos() + x_offset;
-            let y_dst = y_radius * angle.sin() + y_offset;
-
-            //Calculate distance
-            let dx = x_org - x_dst;
-            let dy = y_org - y_dst;
-            let distance = (dx * dx + dy * dy).sqrt();
-
-            let distance_ratio = if distance == 0. {
-                0.
-            } else{
-                max_distance / distance
+            let position = Vec2::new(tf.translation.x, tf.translation.y);
+            let home = grid.home(slot, &win_size);
+            let distance = position.distance(home);
+
+            //snap once close, then the slot carries it along with the grid
+            let position = if distance <= max_distance {
+                home
+            } else {
+                position + (home - position) * max_distance / distance
             };
+            tf.translation.x = position.x;
+            tf.translation.y = position.y;
+        }
+    }
+}
 
-            //Calculate final x/y
-            let x = x_org - dx * distance_ratio;
-            let x = if dx > 0. { x.max(x_dst) } else { x.min(x_dst) };
-            let y = y_org - dy * distance_ratio;
-            let y = if dy > 0. { y.max(y_dst) } else { y.min(y_dst) };
+//sends a settled enemy that dives out of the grid at the player
+fn enemy_attack(
+    mut commands: Commands,
+    time: Res<Time>,
+    win_size: Res<WinSize>,
+    mut grid: ResMut<FormationGrid>,
+    player_query: Query<&Transform, With<Player>>,
+    enemy_query: Query<(Entity, &Transform, &Slot, &EnemyType, &PauseState), (With<Enemy>, Without<FollowPath>, Without<Beaming>)>,
+){
+    grid.attack.tick(time.delta());
+    if !grid.attack.just_finished() {
+        return;
+    }
 
-            // start rotating the formation angle only when sprite are on or close to destination
-            if distance < max_distance * speed.v / 20. {
-                formation.angle = angle;
-            }
+    let attackers: Vec<(Entity, Vec2)> = enemy_query.iter()
+        .filter(|(_, tf, slot, enemy_type, pause)| {
+            //settled in its slot, not still flying back to it
+            let position = Vec2::new(tf.translation.x, tf.translation.y);
+            enemy_type.movement == Movement::Dive && !pause.0 && position.distance(grid.home(slot, &win_size)) < 5.
+        })
+        .map(|(entity, tf, _, _, _)| (entity, Vec2::new(tf.translation.x, tf.translation.y)))
+        .collect();
+    if attackers.is_empty() {
+        return;
+    }
+
+    let mut rng = thread_rng();
+    let (entity, start) = attackers[rng.gen_range(0..attackers.len())];
+    //no player on screen, dive straight down
+    let target = player_query.iter().next()
+        .map(|tf| Vec2::new(tf.translation.x, tf.translation.y))
+        .unwrap_or_else(|| Vec2::new(start.x, -win_size.h / 2. + 50.));
+    commands.entity(entity)
+        .insert(dive_path(start, target, &win_size, rng.gen::<bool>()))
+        .insert(Diving);
+}
+
+//shots left of a Weapon::Burst
+struct BurstFire{
+    left: u32,
+    timer: Timer,
+    aimed: bool,
+}
 
-            tf.translation.x = x;
-            tf.translation.y = y;
+//straight down, or at the player when aimed and there is one
+fn aim(origin: Vec3, player: Option<Vec3>, aimed: bool) -> Vec2 {
+    let down = Vec2::new(0., -1.);
+    match player{
+        Some(player) if aimed => {
+            let direction = (player - origin).truncate();
+            if direction.length_squared() > 0. { direction.normalize() } else { down }
         }
+        _ => down,
     }
 }
 
+fn spawn_enemy_laser(commands: &mut Commands, materials: &Materials, translation: Vec3, direction: Vec2, speed: f32){
+    //the sprite points up
+    let angle = direction.y.atan2(direction.x) - PI / 2.;
+    commands
+        .spawn_bundle(SpriteBundle{
+            material: materials.enemy_laser.clone(),
+            transform: Transform{
+                translation,
+                rotation: Quat::from_rotation_z(angle),
+                scale: Vec3::new(SCALE, SCALE, 1.),
+            },
+            ..Default::default()
+        })
+        .insert(Laser)
+        .insert(FromEnemy)
+        .insert(Velocity{v: direction * speed})
+        .insert(PauseState::default());
+}
+
 fn enemy_fire(
     mut commands: Commands,
     time: Res<Time>,
     materials: Res<Materials>,
-    mut enemy_query: Query<(&Transform,&PauseState, &mut Timer, &LaserSpeed), With<Enemy>>
+    player_query: Query<&Transform, With<Player>>,
+    mut enemy_query: Query<(Entity, &Transform, &PauseState, &mut Timer, &LaserSpeed, &EnemyType, Option<&mut BurstFire>), With<Enemy>>
 ){
-    for (&tf, pause,mut timer, lspeed) in enemy_query.iter_mut(){
+    let player = player_query.iter().next().map(|tf| tf.translation);
+
+    for (entity, &tf, pause, mut timer, lspeed, enemy_type, burst) in enemy_query.iter_mut(){
         timer.tick(time.delta());
-        if !pause.0 && timer.finished() {
-            let x = tf.translation.x;
-            let y = tf.translation.y;
-            //spawn enemy laser sprite
-            commands
-                .spawn_bundle(SpriteBundle{
-                    material: materials.enemy_laser.clone(),
-                    transform: Transform{
-                        translation: Vec3::new(x, y - 15., 0.),
-                        scale: Vec3::new(SCALE, -SCALE, 1.),
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                })
-                .insert(Laser)
-                .insert(FromEnemy)
-                .insert(Speed{v: lspeed.v})
-                .insert(PauseState::default());
+        if pause.0 {
+            continue;
+        }
+        let origin = tf.translation - Vec3::new(0., 15., 0.);
+        //x offset and direction of each laser
+        let mut shots: Vec<(f32, Vec2)> = Vec::new();
+
+        if let Some(mut burst) = burst {
+            burst.timer.tick(time.delta());
+            if burst.timer.just_finished() {
+                shots.push((0., aim(origin, player, burst.aimed)));
+                burst.left -= 1;
+                if burst.left == 0 {
+                    commands.entity(entity).remove::<BurstFire>();
+                }
+            }
+        }
+
+        if timer.finished() {
+            match enemy_type.weapon{
+                Weapon::None => {}
+                Weapon::Single => shots.push((0., aim(origin, player, false))),
+                Weapon::Double => {
+                    shots.push((-12., aim(origin, player, false)));
+                    shots.push((12., aim(origin, player, false)));
+                }
+                Weapon::Aimed => shots.push((0., aim(origin, player, true))),
+                Weapon::Spread{shots: count, angle, aimed} => {
+                    let center = aim(origin, player, aimed);
+                    for i in 0..count{
+                        let offset = (i as f32 - (count - 1) as f32 / 2.) * angle.to_radians();
+                        shots.push((0., (Quat::from_rotation_z(offset) * center.extend(0.)).truncate()));
+                    }
+                }
+                Weapon::Burst{shots: count, interval, aimed} => {
+                    shots.push((0., aim(origin, player, aimed)));
+                    if count > 1 {
+                        commands.entity(entity).insert(BurstFire{
+                            left: count - 1,
+                            timer: Timer::from_seconds(interval, true),
+                            aimed,
+                        });
+                    }
+                }
+            }
+        }
+
+        for (x_offset, direction) in shots{
+            spawn_enemy_laser(&mut commands, &materials, origin + Vec3::new(x_offset, 0., 0.), direction, lspeed.v);
         }
     }
 }
@@ -206,14 +441,14 @@ fn enemy_fire(
 fn enemy_laser_movement(
     mut commands: Commands,
     win_size: Res<WinSize>,
-    mut laser_query: Query<(Entity,&PauseState, &Speed, &mut Transform), (With<Laser>, With<FromEnemy>)>
+    mut laser_query: Query<(Entity,&PauseState, &Velocity, &mut Transform), (With<Laser>, With<FromEnemy>)>
 ){
-    for (entity, pause, speed, mut tf) in laser_query.iter_mut() {
+    for (entity, pause, velocity, mut tf) in laser_query.iter_mut() {
         if !pause.0{
-            tf.translation.y -= speed.v * TIME_STEP;
-            if tf.translation.y < -win_size.h / 2. - 50. {
+            tf.translation += velocity.v.extend(0.) * TIME_STEP;
+            if !win_size.contains(tf.translation, 50.) {
                 commands.entity(entity).despawn();
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,308 @@
+use bevy::prelude::*;
+use crate::{Materials, Player, PlayerState, GameState, Wave, Shield};
+use crate::database::{LoadHighScoresRequest, HighScoresLoaded};
+use crate::powerup::{ActivePowerUps, PowerUpKind};
+use crate::state::ExtraLifeAwarded;
+use crate::turns::Turns;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin{
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(BestScore(0))
+            .insert_resource(ExtraLifeFlash(None))
+            .add_system(hud_best_score_loaded.system())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(hud_spawn.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(hud_score.system())
+                    .with_system(hud_lives.system())
+                    .with_system(hud_wave.system())
+                    .with_system(hud_power_ups.system())
+                    .with_system(hud_extra_life.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::HighScoreEntry)
+                    .with_system(hud_despawn.system())
+            );
+    }
+}
+
+const HUD_FONT_SIZE: f32 = 24.0;
+//the player sprite shrunk down
+const LIFE_ICON_SIZE: (f32, f32) = (36., 19.);
+//life icons in the row, lives past it are not shown
+const MAX_LIFE_ICONS: u32 = 8;
+//seconds the extra life text flashes, and each blink of it
+const EXTRA_LIFE_FLASH_TIME: f32 = 2.;
+const EXTRA_LIFE_BLINK_TIME: f32 = 0.2;
+
+//best score in the score store, loaded when a run starts
+struct BestScore(u32);
+
+//the root, everything else is a child of it
+struct Hud;
+struct HudScore;
+struct HudBestScore;
+struct HudWave;
+//the icons are all there from the start and hidden when the life isn't
+struct LifeIcon(u32);
+struct HudPowerUps;
+//hidden unless a score threshold just gave a life
+struct HudExtraLife;
+struct ExtraLifeFlash(Option<Timer>);
+
+//a red label followed by the white value, placed absolutely on the screen
+fn hud_text(font: &Handle<Font>, label: &str, value: String, position: Rect<Val>) -> TextBundle {
+    TextBundle{
+        text: Text{
+            sections: vec![
+                TextSection{
+                    value: label.to_string(),
+                    style: TextStyle{
+                        font: font.clone(),
+                        font_size: HUD_FONT_SIZE,
+                        color: Color::rgb(0.9, 0.2, 0.2),
+                    },
+                },
+                TextSection{
+                    value,
+                    style: TextStyle{
+                        font: font.clone(),
+                        font_size: HUD_FONT_SIZE,
+                        color: Color::WHITE,
+                    },
+                },
+            ],
+            ..Default::default()
+        },
+        style: Style{
+            position_type: PositionType::Absolute,
+            position,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn life_icon(materials: &Materials, shown: bool) -> ImageBundle {
+    ImageBundle{
+        visible: Visible{
+            is_visible: shown,
+            is_transparent: true,
+        },
+        style: Style{
+            size: Size::new(Val::Px(LIFE_ICON_SIZE.0), Val::Px(LIFE_ICON_SIZE.1)),
+            margin: Rect{
+                right: Val::Px(4.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        material: materials.player.clone(),
+        ..Default::default()
+    }
+}
+
+fn hud_spawn(
+    mut commands: Commands,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    mut load_requests: EventWriter<LoadHighScoresRequest>,
+    asset_server: Res<AssetServer>,
+    materials: Res<Materials>,
+    player_state: Res<PlayerState>,
+    best_score: Res<BestScore>,
+    wave: Res<Wave>,
+    turns: Res<Turns>,
+){
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    load_requests.send(LoadHighScoresRequest(1));
+
+    commands
+        .spawn_bundle(NodeBundle{
+            style: Style{
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            material: color_materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .insert(Hud)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(hud_text(&font, &turns.score_label(), player_state.score.to_string(), Rect{
+                    left: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..Default::default()
+                }))
+                .insert(HudScore);
+            parent
+                .spawn_bundle(hud_text(&font, "HIGH ", best_score.0.max(player_state.score).to_string(), Rect{
+                    left: Val::Percent(40.0),
+                    top: Val::Px(10.0),
+                    ..Default::default()
+                }))
+                .insert(HudBestScore);
+            parent
+                .spawn_bundle(hud_text(&font, "WAVE ", wave.number.to_string(), Rect{
+                    right: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..Default::default()
+                }))
+                .insert(HudWave);
+            parent
+                .spawn_bundle(hud_text(&font, "", String::new(), Rect{
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(40.0),
+                    ..Default::default()
+                }))
+                .insert(HudPowerUps);
+            parent
+                .spawn_bundle(hud_text(&font, "EXTRA LIFE", String::new(), Rect{
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(70.0),
+                    ..Default::default()
+                }))
+                .insert(Visible{
+                    is_visible: false,
+                    is_transparent: true,
+                })
+                .insert(HudExtraLife);
+            parent
+                .spawn_bundle(NodeBundle{
+                    style: Style{
+                        position_type: PositionType::Absolute,
+                        position: Rect{
+                            left: Val::Px(10.0),
+                            bottom: Val::Px(10.0),
+                            ..Default::default()
+                        },
+                        flex_direction: FlexDirection::Row,
+                        ..Default::default()
+                    },
+                    material: color_materials.add(Color::NONE.into()),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    for i in 0..MAX_LIFE_ICONS{
+                        parent
+                            .spawn_bundle(life_icon(&materials, i < player_state.lifes))
+                            .insert(LifeIcon(i));
+                    }
+                });
+        });
+}
+
+fn hud_best_score_loaded(
+    mut best_score: ResMut<BestScore>,
+    mut loaded: EventReader<HighScoresLoaded>,
+){
+    for HighScoresLoaded(result) in loaded.iter(){
+        //whoever asked, the first row is the best score
+        if let Ok(rows) = result {
+            best_score.0 = rows.first().map(|row| row.score).unwrap_or(0);
+        }
+    }
+}
+
+fn hud_score(
+    player_state: Res<PlayerState>,
+    best_score: Res<BestScore>,
+    turns: Res<Turns>,
+    mut score_query: Query<&mut Text, (With<HudScore>, Without<HudBestScore>)>,
+    mut best_query: Query<&mut Text, (With<HudBestScore>, Without<HudScore>)>,
+){
+    if !player_state.is_changed() && !best_score.is_changed() && !turns.is_changed() {
+        return;
+    }
+    for mut text in score_query.iter_mut(){
+        //whose turn it is in the two player mode
+        text.sections[0].value = turns.score_label();
+        text.sections[1].value = player_state.score.to_string();
+    }
+    for mut text in best_query.iter_mut(){
+        text.sections[1].value = best_score.0.max(player_state.score).to_string();
+    }
+}
+
+fn hud_lives(
+    player_state: Res<PlayerState>,
+    mut query: Query<(&LifeIcon, &mut Visible)>,
+){
+    if !player_state.is_changed() {
+        return;
+    }
+    for (icon, mut visible) in query.iter_mut(){
+        visible.is_visible = icon.0 < player_state.lifes;
+    }
+}
+
+fn hud_wave(
+    wave: Res<Wave>,
+    mut query: Query<&mut Text, With<HudWave>>,
+){
+    if !wave.is_changed() {
+        return;
+    }
+    for mut text in query.iter_mut(){
+        text.sections[1].value = wave.number.to_string();
+    }
+}
+
+fn hud_power_ups(
+    power_ups: Res<ActivePowerUps>,
+    shield_query: Query<&Shield, With<Player>>,
+    mut query: Query<&mut Text, With<HudPowerUps>>,
+){
+    let mut lines: Vec<String> = power_ups.remaining().iter()
+        .map(|(kind, left)| format!("{} {:.0}s", kind.label(), left.ceil()))
+        .collect();
+    if let Ok(shield) = shield_query.single(){
+        lines.push(format!("{} x{}", PowerUpKind::Shield.label(), shield.hits));
+    }
+
+    for mut text in query.iter_mut(){
+        text.sections[1].value = lines.join("\n");
+    }
+}
+
+fn hud_extra_life(
+    time: Res<Time>,
+    mut flash: ResMut<ExtraLifeFlash>,
+    mut awarded: EventReader<ExtraLifeAwarded>,
+    mut query: Query<&mut Visible, With<HudExtraLife>>,
+){
+    if awarded.iter().next().is_some() {
+        flash.0 = Some(Timer::from_seconds(EXTRA_LIFE_FLASH_TIME, false));
+    }
+
+    let shown = match flash.0.as_mut(){
+        Some(timer) => {
+            timer.tick(time.delta());
+            !timer.finished() && (timer.elapsed_secs() / EXTRA_LIFE_BLINK_TIME) as u32 % 2 == 0
+        }
+        None => false,
+    };
+    if flash.0.as_ref().map_or(false, |timer| timer.finished()) {
+        flash.0 = None;
+    }
+    for mut visible in query.iter_mut(){
+        visible.is_visible = shown;
+    }
+}
+
+fn hud_despawn(
+    mut commands: Commands,
+    mut flash: ResMut<ExtraLifeFlash>,
+    query: Query<Entity, With<Hud>>,
+){
+    flash.0 = None;
+    for entity in query.iter(){
+        commands.entity(entity).despawn_recursive();
+    }
+}
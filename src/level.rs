@@ -0,0 +1,508 @@
+use bevy::prelude::*;
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset, LoadState};
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+use std::collections::HashMap;
+use crate::config::Config;
+use crate::Slot;
+
+pub struct LevelPlugin;
+
+impl Plugin for LevelPlugin{
+    fn build(&self, app: &mut AppBuilder) {
+        let level = app.world().get_resource::<Config>().expect("Config has to be inserted before the LevelPlugin").level.clone();
+
+        app
+            .insert_resource(LevelPath(level))
+            .add_asset::<Level>()
+            .init_asset_loader::<LevelLoader>()
+            .add_startup_system(level_load.system());
+    }
+}
+
+//a level file in assets/levels, the waves are played in order
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "6c7f3a52-2b0e-4a8e-9d7b-6a0b5f8e4c21"]
+pub struct Level{
+    //referred to by name in the wave compositions
+    pub enemy_types: HashMap<String, EnemyType>,
+    pub waves: Vec<WaveDef>,
+    //how the last wave gets harder once all waves are played
+    pub endless: Endless,
+    #[serde(default)]
+    pub power_ups: PowerUpSettings,
+    #[serde(default)]
+    pub extra_lives: ExtraLives,
+    #[serde(default)]
+    pub scoring: ScoringSettings,
+}
+
+//also the component every enemy carries
+#[derive(Deserialize, Clone)]
+pub struct EnemyType{
+    pub hit_points: u32,
+    //added to the player score on the kill
+    pub score: u32,
+    pub sprite: String,
+    #[serde(default = "default_tint")]
+    pub tint: (f32, f32, f32),
+    //on top of the sprite scale every ship has
+    #[serde(default = "default_factor")]
+    pub scale: f32,
+    //multiplies the wave speed
+    #[serde(default = "default_factor")]
+    pub speed: f32,
+    pub movement: Movement,
+    pub weapon: Weapon,
+    //comes down now and then to capture the player ship
+    #[serde(default)]
+    pub tractor_beam: bool,
+    //its key in enemy_types, filled in on load
+    #[serde(skip)]
+    pub name: String,
+}
+
+fn default_tint() -> (f32, f32, f32) {
+    (1., 1., 1.)
+}
+
+fn default_factor() -> f32 {
+    1.
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub enum Movement{
+    //stay in the formation grid
+    Hold,
+    //picked from the grid to dive at the player
+    Dive,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub enum Weapon{
+    None,
+    //straight down
+    Single,
+    Double,
+    //one shot at the player
+    Aimed,
+    //a fan of shots, angle is in degrees between two of them
+    Spread{
+        shots: u32,
+        angle: f32,
+        #[serde(default)]
+        aimed: bool,
+    },
+    //shots one after the other, interval in seconds
+    Burst{
+        shots: u32,
+        interval: f32,
+        #[serde(default)]
+        aimed: bool,
+    },
+}
+
+//how a wave is made up
+#[derive(Deserialize, Clone)]
+pub struct WaveDef{
+    //enemy type and count, spawned in this order and laid out
+    //in the grid from the top row down
+    pub composition: Vec<(String, u32)>,
+    //slots in a grid row
+    pub columns: u32,
+    //enemies on screen at the same time
+    pub max_active: u32,
+    //enemies flying in along the same entry path
+    pub formation_members: u32,
+    pub speed: f32,
+    pub laser_speed: f32,
+    //seconds between two shots of an enemy
+    pub fire_interval: f32,
+}
+
+//applied once for every wave past the last one of the level
+#[derive(Deserialize, Clone)]
+pub struct Endless{
+    pub enemies: u32,
+    pub speed: f32,
+    pub max_speed: f32,
+    pub fire_interval: f32,
+    pub min_fire_interval: f32,
+}
+
+//pickups dropped by destroyed enemies
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct PowerUpSettings{
+    //between 0 and 1, rolled for every destroyed enemy
+    pub drop_chance: f32,
+    //seconds a timed power-up lasts
+    pub duration: f32,
+    pub fall_speed: f32,
+    pub score_multiplier: u32,
+    //hits a shield takes before it breaks
+    pub shield_hits: u32,
+}
+
+impl Default for PowerUpSettings{
+    fn default() -> Self {
+        PowerUpSettings{
+            drop_chance: 0.08,
+            duration: 10.,
+            fall_speed: 150.,
+            score_multiplier: 2,
+            shield_hits: 3,
+        }
+    }
+}
+
+//bonus lives for reaching score thresholds
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ExtraLives{
+    pub first: u32,
+    //after the first one, 0 for no more
+    pub every: u32,
+    //no bonus life above it, power-ups included
+    pub max_lives: u32,
+}
+
+impl Default for ExtraLives{
+    fn default() -> Self {
+        ExtraLives{
+            first: 20000,
+            every: 70000,
+            max_lives: 5,
+        }
+    }
+}
+
+impl ExtraLives{
+    //score needed for the bonus life after `awarded` of them
+    pub fn threshold(&self, awarded: u32) -> Option<u32> {
+        match awarded{
+            0 => Some(self.first),
+            _ if self.every == 0 => None,
+            n => self.every.checked_mul(n).and_then(|extra| extra.checked_add(self.first)),
+        }
+    }
+}
+
+//bonuses on top of the score of the enemy type, see scoring.rs
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ScoringSettings{
+    //for an enemy shot down while it attacks out of the grid
+    pub dive_multiplier: u32,
+    //seconds the next kill has to come within to keep the combo going
+    pub combo_window: f32,
+    //kills in a row for each step up of the combo multiplier
+    pub combo_step: u32,
+    pub max_combo_multiplier: u32,
+    //points for a wave cleared without a miss, less with misses
+    pub accuracy_bonus: u32,
+}
+
+impl Default for ScoringSettings{
+    fn default() -> Self {
+        ScoringSettings{
+            dive_multiplier: 2,
+            combo_window: 1.5,
+            combo_step: 4,
+            max_combo_multiplier: 4,
+            accuracy_bonus: 2000,
+        }
+    }
+}
+
+//used when the level file can't be loaded, same as levels/default.level.ron without its later waves
+impl Default for Level{
+    fn default() -> Self {
+        let mut enemy_types = HashMap::new();
+        enemy_types.insert("grunt".to_string(), EnemyType{
+            hit_points: 1,
+            score: 50,
+            sprite: "enemy_b_01.png".to_string(),
+            tint: default_tint(),
+            scale: 1.,
+            speed: 1.,
+            movement: Movement::Hold,
+            weapon: Weapon::Single,
+            tractor_beam: false,
+            name: "grunt".to_string(),
+        });
+        Level{
+            enemy_types,
+            waves: vec![WaveDef{
+                composition: vec![("grunt".to_string(), 8)],
+                columns: 8,
+                max_active: 8,
+                formation_members: 2,
+                speed: 500.,
+                laser_speed: 500.,
+                fire_interval: 0.9,
+            }],
+            endless: Endless{
+                enemies: 2,
+                speed: 1.05,
+                max_speed: 1000.,
+                fire_interval: 0.92,
+                min_fire_interval: 0.3,
+            },
+            power_ups: PowerUpSettings::default(),
+            extra_lives: ExtraLives::default(),
+            scoring: ScoringSettings::default(),
+        }
+    }
+}
+
+impl Level{
+    //values the game would stall on, a wave that never spawns or never clears or an
+    //enemy that can't fire anymore
+    pub fn validate(&self) -> Result<(), String> {
+        if self.waves.is_empty() {
+            return Err("the level has no waves".to_string());
+        }
+        for (name, enemy_type) in self.enemy_types.iter(){
+            if enemy_type.hit_points == 0 {
+                return Err(format!("enemy type {} has no hit points", name));
+            }
+            if let Weapon::Burst{interval, ..} = enemy_type.weapon {
+                if !(interval > 0.) {
+                    return Err(format!("enemy type {} has a burst interval of {}", name, interval));
+                }
+            }
+        }
+        for (i, wave) in self.waves.iter().enumerate(){
+            if wave.max_active == 0 {
+                return Err(format!("wave {} has max_active 0", i + 1));
+            }
+            if wave.columns == 0 {
+                return Err(format!("wave {} has no columns", i + 1));
+            }
+            //a zero duration repeating timer never fires again
+            if !(wave.fire_interval > 0.) {
+                return Err(format!("wave {} has a fire_interval of {}", i + 1, wave.fire_interval));
+            }
+            //the spawn would skip them and the wave never clears
+            if let Some((name, _)) = wave.composition.iter().find(|(name, _)| !self.enemy_types.contains_key(name)){
+                return Err(format!("wave {} has the unknown enemy type {}", i + 1, name));
+            }
+        }
+        Ok(())
+    }
+
+    //waves start at 1
+    pub fn wave(&self, number: u32) -> WaveDef {
+        let index = number.max(1) as usize - 1;
+        if let Some(wave) = self.waves.get(index){
+            return wave.clone();
+        }
+
+        let mut wave = self.waves.last().cloned().unwrap_or_else(|| Level::default().waves[0].clone());
+        let extra = (index + 1 - self.waves.len().max(1)) as i32;
+        //the extra enemies are of the last type of the wave, the bottom rows of the grid
+        if let Some((_, count)) = wave.composition.last_mut(){
+            *count += self.endless.enemies * extra as u32;
+        }
+        wave.speed = (wave.speed * self.endless.speed.powi(extra)).min(self.endless.max_speed);
+        wave.fire_interval = (wave.fire_interval * self.endless.fire_interval.powi(extra)).max(self.endless.min_fire_interval);
+        wave
+    }
+}
+
+impl WaveDef{
+    //enemy type names and their grid slots in spawn order, every type starts a new
+    //row and the rows are centered
+    pub fn spawn_queue(&self) -> Vec<(String, Slot)> {
+        let columns = self.columns.max(1);
+        let mut queue = Vec::new();
+        let mut row = 0;
+        for (name, count) in self.composition.iter(){
+            let mut left = *count;
+            while left > 0 {
+                let in_row = left.min(columns);
+                for i in 0..in_row{
+                    let x = i as f32 - (in_row - 1) as f32 / 2.;
+                    queue.push((name.clone(), Slot{ x, row }));
+                }
+                left -= in_row;
+                row += 1;
+            }
+        }
+        queue
+    }
+}
+
+struct LevelPath(String);
+
+pub struct CurrentLevel{
+    pub handle: Handle<Level>,
+    fallback: Level,
+}
+
+impl CurrentLevel{
+    //None while the file is still loading
+    pub fn get<'a>(&'a self, levels: &'a Assets<Level>, asset_server: &AssetServer) -> Option<&'a Level> {
+        match levels.get(&self.handle){
+            Some(level) => Some(level),
+            None if asset_server.get_load_state(&self.handle) == LoadState::Failed => Some(&self.fallback),
+            None => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct LevelLoader;
+
+impl AssetLoader for LevelLoader{
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let mut level: Level = ron::de::from_bytes(bytes)?;
+            for (name, enemy_type) in level.enemy_types.iter_mut(){
+                enemy_type.name = name.clone();
+            }
+            level.validate().map_err(|e| anyhow::anyhow!("invalid level file: {}", e))?;
+            load_context.set_default_asset(LoadedAsset::new(level));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+fn level_load(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    level_path: Res<LevelPath>,
+){
+    //designers can tune the level files while the game runs
+    #[cfg(debug_assertions)]
+    asset_server.watch_for_changes().unwrap();
+
+    commands.insert_resource(CurrentLevel{
+        handle: asset_server.load(level_path.0.as_str()),
+        fallback: Level::default(),
+    });
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn wave_def(composition: &[(&str, u32)], columns: u32) -> WaveDef {
+        WaveDef{
+            composition: composition.iter().map(|(name, count)| (name.to_string(), *count)).collect(),
+            columns,
+            ..Level::default().waves[0].clone()
+        }
+    }
+
+    #[test]
+    fn wave_within_the_level(){
+        let level = Level::default();
+        assert_eq!(level.wave(0).composition, level.wave(1).composition);
+        assert_eq!(level.wave(1).composition, vec![("grunt".to_string(), 8)]);
+        assert_eq!(level.wave(1).speed, 500.);
+    }
+
+    #[test]
+    fn wave_endless_scaling(){
+        let level = Level::default();
+        let wave = level.wave(3);
+        assert_eq!(wave.composition, vec![("grunt".to_string(), 12)]);
+        assert!((wave.speed - 500. * 1.05f32.powi(2)).abs() < 1e-3);
+        assert!((wave.fire_interval - 0.9 * 0.92f32.powi(2)).abs() < 1e-6);
+
+        //far enough in the speed and the fire interval hit their limits
+        let wave = level.wave(100);
+        assert_eq!(wave.speed, 1000.);
+        assert_eq!(wave.fire_interval, 0.3);
+    }
+
+    #[test]
+    fn spawn_queue_rows(){
+        let queue = wave_def(&[("boss", 2), ("grunt", 5)], 4).spawn_queue();
+        let slots: Vec<(&str, f32, u32)> = queue.iter().map(|(name, slot)| (name.as_str(), slot.x, slot.row)).collect();
+        assert_eq!(slots, vec![
+            //every type starts a new row, rows are centered
+            ("boss", -0.5, 0), ("boss", 0.5, 0),
+            ("grunt", -1.5, 1), ("grunt", -0.5, 1), ("grunt", 0.5, 1), ("grunt", 1.5, 1),
+            ("grunt", 0., 2),
+        ]);
+    }
+
+    #[test]
+    fn spawn_queue_without_columns(){
+        let queue = wave_def(&[("grunt", 2)], 0).spawn_queue();
+        let rows: Vec<u32> = queue.iter().map(|(_, slot)| slot.row).collect();
+        assert_eq!(rows, vec![0, 1]);
+    }
+
+    #[test]
+    fn validate_stalling_values(){
+        assert!(Level::default().validate().is_ok());
+
+        let mut level = Level::default();
+        level.waves.clear();
+        assert!(level.validate().is_err());
+
+        let mut level = Level::default();
+        level.waves[0].max_active = 0;
+        assert!(level.validate().is_err());
+
+        let mut level = Level::default();
+        level.waves[0].columns = 0;
+        assert!(level.validate().is_err());
+
+        let mut level = Level::default();
+        level.waves[0].fire_interval = 0.;
+        assert!(level.validate().is_err());
+
+        let mut level = Level::default();
+        level.waves[0].fire_interval = -1.;
+        assert!(level.validate().is_err());
+
+        let mut level = Level::default();
+        level.enemy_types.get_mut("grunt").unwrap().hit_points = 0;
+        assert!(level.validate().is_err());
+
+        let mut level = Level::default();
+        level.enemy_types.get_mut("grunt").unwrap().weapon = Weapon::Burst{ shots: 3, interval: 0., aimed: false };
+        assert!(level.validate().is_err());
+
+        let mut level = Level::default();
+        level.waves[0].composition.push(("ghost".to_string(), 2));
+        assert!(level.validate().is_err());
+    }
+
+    #[test]
+    fn default_level_file_is_valid(){
+        let level: Level = ron::de::from_str(include_str!("../assets/levels/default.level.ron")).unwrap();
+        assert!(level.validate().is_ok());
+    }
+
+    #[test]
+    fn extra_life_thresholds(){
+        let extra_lives = ExtraLives{ first: 20000, every: 70000, max_lives: 5 };
+        assert_eq!(extra_lives.threshold(0), Some(20000));
+        assert_eq!(extra_lives.threshold(1), Some(90000));
+        assert_eq!(extra_lives.threshold(2), Some(160000));
+        //past u32 there is no next one
+        assert_eq!(extra_lives.threshold(u32::MAX), None);
+    }
+
+    #[test]
+    fn extra_life_only_once(){
+        let extra_lives = ExtraLives{ first: 20000, every: 0, max_lives: 5 };
+        assert_eq!(extra_lives.threshold(0), Some(20000));
+        assert_eq!(extra_lives.threshold(1), None);
+    }
+}
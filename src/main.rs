@@ -5,6 +5,18 @@ mod enemy;
 mod window;
 mod ui;
 mod state;
+mod menu;
+mod database;
+mod store;
+mod config;
+mod level;
+mod path;
+mod tractor;
+mod powerup;
+mod hud;
+mod scoring;
+mod stats;
+mod turns;
 
 use bevy::prelude::*;
 use crate::{enemy::EnemyPlugin, player::PlayerPlugin};
@@ -21,27 +33,43 @@ use bevy_egui::egui::CtxRef;
 use crate::window::WindowPlugin;
 use crate::ui::UiPlugin;
 use crate::state::StatePlugin;
+use crate::menu::MenuPlugin;
+use crate::database::DatabasePlugin;
+use crate::config::Config;
+use crate::level::LevelPlugin;
+use crate::path::PathPlugin;
+use crate::tractor::TractorPlugin;
+use crate::powerup::PowerUpPlugin;
+use crate::hud::HudPlugin;
+use crate::scoring::ScoringPlugin;
+use crate::stats::StatsPlugin;
+use crate::turns::TurnsPlugin;
 
 const PLAYER_SPRITE: &str = "player_c_01.png";
 const PLAYER_LASER_SPRITE: &str = "laser_a_01.png";
-const ENEMY_SPRITE: &str = "enemy_b_01.png";
 const ENEMY_LASER_SPRITE: &str = "laser_b_01.png";
 const EXPLOSION_SHEET: &str = "explo_a_sheet.png";
+const LOGO_SPRITE: &str = "gildaga-logo-tr.png";
 const FIRING_SFX: &str = "Audio/Galaga_Firing_Sound_Effect.mp3";
 const KILL_SFX: &str = "Audio/Galaga_Kill_Enemy_Sound_Effect.mp3";
 const DEAD_SFX: &str = "Audio/m01se_03hit1.mp3";
 const GAMEOVER_SFX: &str = "Audio/GALAGA_NAME_ENTRY_MUSIC_ARRANGE_VERSION.mp3";
+const THEME_MUSIC: &str = "Audio/Galaga_Theme_Song.mp3";
+const EXTRA_LIFE_SFX: &str = "Audio/Galaga_Coin_Sound_Effect.mp3";
 const TIME_STEP: f32 = 1. / 60.;
 const SCALE: f32 = 0.5;
-const MAX_ENEMIES: u32 = 4;
-const MAX_FORMATION_MEMBERS: u32 = 2;
+const WAVE_BANNER_TIME: f32 = 2.;
 const PLAYER_RESPAWN_DELAY: f64 = 2.;
+//seconds of invulnerability after a (re)spawn
+const PLAYER_INVULNERABLE_TIME: f32 = 1.5;
+const GAMEOVER_DELAY: f32 = 2.;
+const HIGHSCORE_ROWS: u32 = 10;
+const NAME_PLACEHOLDER: &str = "<write down your name here>";
 const BEVY_TEXTURE_ID: u64 = 0;
 
 pub struct Materials{
     player: Handle<ColorMaterial>,
     player_laser: Handle<ColorMaterial>,
-    enemy: Handle<ColorMaterial>,
     enemy_laser: Handle<ColorMaterial>,
     normal: Handle<ColorMaterial>,
     hovered: Handle<ColorMaterial>,
@@ -60,7 +88,6 @@ impl FromWorld for Materials{
         let mut material = Materials{
             player: materials.add(asset_server.load(PLAYER_SPRITE).into()),
             player_laser: materials.add(asset_server.load(PLAYER_LASER_SPRITE).into()),
-            enemy: materials.add(asset_server.load(ENEMY_SPRITE).into()),
             enemy_laser: materials.add(asset_server.load(ENEMY_LASER_SPRITE).into()),
             normal: materials.add(Color::rgb(0.15, 0.15, 0.15).into()),
             hovered: materials.add(Color::rgb(0.25, 0.25, 0.25).into()),
@@ -80,15 +107,51 @@ struct WinSize{
     w: f32,
     h: f32,
 }
+impl WinSize{
+    //margin lets sprites leave the screen completely first
+    fn contains(&self, translation: Vec3, margin: f32) -> bool {
+        translation.x.abs() < self.w / 2. + margin && translation.y.abs() < self.h / 2. + margin
+    }
+}
 struct ActiveEnemies(u32);
+struct Wave{
+    number: u32,
+    //enemy types of this wave that still have to fly in and their slots, in spawn order
+    to_spawn: Vec<(String, Slot)>,
+    //the "Wave N" banner, no enemies spawn while it shows
+    banner: Timer,
+}
+
+impl Default for Wave{
+    fn default() -> Self {
+        Wave{
+            number: 0,
+            to_spawn: Vec::new(),
+            banner: Timer::from_seconds(WAVE_BANNER_TIME, false),
+        }
+    }
+}
+
+struct WaveBanner;
+
+//id of the score row saved during this session, highlighted in the high score table
+struct LastSavedScore(Option<u64>);
 
 struct PlayerState{
     on: bool,
     last_shot: f64,
-    invurnerable_timer: Timer,
     username: String,
     lifes: u32,
     score: u32,
+    //unique per run, the score store refuses a second save with the same id
+    run_id: String,
+    submitted: bool,
+    //a captured ship was rescued, lost again with the next life
+    dual: bool,
+    //score thresholds passed so far, see level::ExtraLives
+    extra_lives_awarded: u32,
+    //died without a life left
+    game_over: bool,
 }
 
 impl Default for PlayerState{
@@ -96,10 +159,14 @@ impl Default for PlayerState{
         Self{
             on: false,
             last_shot: 0.,
-            invurnerable_timer: Timer::from_seconds(0.0, false),
-            username: "<write down your name here>".to_string(),
+            username: String::new(),
             lifes: 3,
             score: 0,
+            run_id: format!("{:016x}{:016x}", rand::random::<u64>(), rand::random::<u64>()),
+            submitted: false,
+            dual: false,
+            extra_lives_awarded: 0,
+            game_over: false,
         }
     }
 }
@@ -107,11 +174,12 @@ impl PlayerState{
     fn shot_or_dead(&mut self, time: f64) -> bool{
         self.on = false;
         self.last_shot = time;
-        self.invurnerable_timer = Timer::from_seconds(0.0, false);
+        self.dual = false;
         if self.lifes != 0{
             self.lifes -= 1;
         }
         else{
+            self.game_over = true;
             return true
         }
         false
@@ -120,11 +188,9 @@ impl PlayerState{
     fn spawned(&mut self){
         self.on = true;
         self.last_shot = 0.;
-        self.invurnerable_timer = Timer::from_seconds(1.5, false);
     }
 }
 
-struct GameOverToSpawn;
 #[derive(Inspectable)]
 struct GameOverText;
 
@@ -137,18 +203,52 @@ struct Laser;
 struct Player;
 struct PlayerReadyFire(bool);
 struct FromPlayer;
+//the player flies a rescued ship next to its own
+struct DualFighter;
+//nothing hurts the player until the timer ends, the ship blinks meanwhile
+struct Invulnerable(Timer);
+//takes the hits instead of the player until none are left
+struct Shield{
+    hits: u32,
+    //the ShieldOverlay child
+    overlay: Entity,
+}
+//the see-through box around a shielded ship
+struct ShieldOverlay;
 
 #[derive(Inspectable, Default)]
 struct Enemy;
 struct FromEnemy;
+//lasers an enemy still takes, the enemy type gives the starting value
+struct HitPoints(u32);
+//home of an enemy in the formation grid, x counts slots from the center
+#[derive(Clone, Copy)]
+struct Slot{
+    x: f32,
+    row: u32,
+}
+//out of the grid attacking the player, worth more shot down meanwhile
+struct Diving;
+//a boss carrying the player ship its tractor beam caught
+struct HoldsCapturedShip(Entity);
 
 struct Explosion;
 struct ExplosionToSpawn(Vec3);
+//where an enemy was destroyed, it may drop a power-up there
+struct PowerUpToSpawn(Vec3);
 
 #[derive(Inspectable)]
 struct PauseState(bool);
-#[derive(Inspectable)]
-struct GameState(String);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum GameState{
+    MainMenu,
+    HighScores,
+    Options,
+    Playing,
+    Paused,
+    GameOver,
+    HighScoreEntry,
+}
 
 impl Default for PauseState{
     fn default() -> Self {
@@ -180,6 +280,12 @@ impl Default for LaserSpeed{
     }
 }
 
+//direction and speed of a laser, in pixels per second
+#[derive(Inspectable, Default)]
+struct Velocity{
+    v: Vec2,
+}
+
 #[derive(Inspectable, Default)]
 struct PauseText;
 struct CheatSheetTimer{
@@ -192,6 +298,12 @@ impl Default for CheatSheetTimer{
         }
     }
 }
+struct GameOverTimer(Timer);
+impl Default for GameOverTimer{
+    fn default() -> Self {
+        GameOverTimer(Timer::from_seconds(GAMEOVER_DELAY, false))
+    }
+}
 
 fn main() {
     let mut app = App::build();
@@ -199,7 +311,7 @@ fn main() {
 
         app
         .insert_resource(ClearColor(Color::rgb(0.04,0.04,0.04)))
-        .insert_resource(GameState("active".to_string()))
+        .add_state(GameState::MainMenu)
         .insert_resource(WindowDescriptor{
             title: "Rust Invaders".to_string(),
             width: 598.,
@@ -207,8 +319,12 @@ fn main() {
             ..Default::default()
         })
         .insert_resource(ActiveEnemies(0))
+        .insert_resource(Wave::default())
+        .insert_resource(LastSavedScore(None))
         .insert_resource(CheatSheetTimer::default())
+        .insert_resource(GameOverTimer::default())
         .add_plugins(DefaultPlugins)
+        .insert_resource(Config::load())
         .add_plugin(EguiPlugin)
         .init_resource::<Materials>()
         .add_plugin(PlayerPlugin)
@@ -216,6 +332,16 @@ fn main() {
         .add_plugin(WindowPlugin)
         .add_plugin(UiPlugin)
         .add_plugin(StatePlugin)
+        .add_plugin(MenuPlugin)
+        .add_plugin(DatabasePlugin)
+        .add_plugin(LevelPlugin)
+        .add_plugin(PathPlugin)
+        .add_plugin(TractorPlugin)
+        .add_plugin(PowerUpPlugin)
+        .add_plugin(HudPlugin)
+        .add_plugin(ScoringPlugin)
+        .add_plugin(StatsPlugin)
+        .add_plugin(TurnsPlugin)
         .add_plugin(InspectorPlugin::<InspectorQuery<(Entity), With<Enemy>>>::new())
         .add_plugin(InspectorPlugin::<InspectorQuerySingle<Entity, With<PauseText>>>::new())
         .add_plugin(InspectorPlugin::<InspectorQuery<Entity, (With<GameOverText>)>>::new())
@@ -229,6 +355,7 @@ fn main() {
         registry.register::<Speed>();
         registry.register::<PauseState>();
         registry.register::<LaserSpeed>();
+        registry.register::<Velocity>();
         registry.register::<GameOverText>();
 
         app.run();
@@ -0,0 +1,376 @@
+use bevy::prelude::*;
+use bevy::app::AppExit;
+use std::cmp::Ordering;
+use crate::{GameState, Materials, LOGO_SPRITE, THEME_MUSIC, HIGHSCORE_ROWS, LastSavedScore};
+use crate::database::{LoadHighScoresRequest, HighScoresLoaded};
+use crate::ui::NameEntryMode;
+use crate::turns::Turns;
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin{
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(MenuMusic(false))
+            .add_system(menu_button_system.system())
+            .add_system(menu_navigation.system())
+            .add_system_set(
+                SystemSet::on_enter(GameState::MainMenu)
+                    .with_system(main_menu_enter.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::MainMenu)
+                    .with_system(menu_exit.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::HighScores)
+                    .with_system(highscores_enter.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::HighScores)
+                    .with_system(highscores_loaded.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::HighScores)
+                    .with_system(menu_exit.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Options)
+                    .with_system(options_enter.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Options)
+                    .with_system(menu_exit.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(menu_music_stop.system())
+            );
+    }
+}
+
+//the theme keeps playing while browsing the menu screens, only restart it after a game
+struct MenuMusic(bool);
+
+//root node of every menu screen, despawned when the screen is left
+pub struct MenuScreen;
+
+//text filled in once the scores are loaded
+struct HighScoreTable;
+
+#[derive(Clone, Copy)]
+pub enum MenuButton{
+    Start,
+    //alternating turns, like the arcade
+    TwoPlayers,
+    HighScores,
+    Options,
+    NameEntry,
+    Back,
+    Quit,
+}
+
+fn main_menu_enter(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    mut menu_music: ResMut<MenuMusic>,
+    mut cmaterials: ResMut<Assets<ColorMaterial>>,
+    materials: Res<Materials>,
+){
+    if !menu_music.0 {
+        let music = asset_server.load(THEME_MUSIC);
+        audio.play(music);
+        menu_music.0 = true;
+    }
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let logo = cmaterials.add(asset_server.load(LOGO_SPRITE).into());
+    spawn_menu_screen(&mut commands, &mut cmaterials)
+        .with_children(|parent| {
+            parent.spawn_bundle(ImageBundle {
+                style: Style {
+                    size: Size::new(Val::Px(300.0), Val::Px(300.0)),
+                    margin: Rect::all(Val::Px(10.0)),
+                    ..Default::default()
+                },
+                material: logo,
+                ..Default::default()
+            });
+            spawn_menu_button(parent, &materials, font.clone(), "Start", MenuButton::Start);
+            spawn_menu_button(parent, &materials, font.clone(), "2 Players", MenuButton::TwoPlayers);
+            spawn_menu_button(parent, &materials, font.clone(), "High Scores", MenuButton::HighScores);
+            spawn_menu_button(parent, &materials, font.clone(), "Options", MenuButton::Options);
+            spawn_menu_button(parent, &materials, font.clone(), "Quit", MenuButton::Quit);
+        });
+}
+
+fn highscores_enter(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut cmaterials: ResMut<Assets<ColorMaterial>>,
+    materials: Res<Materials>,
+    mut load_requests: EventWriter<LoadHighScoresRequest>,
+){
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    spawn_menu_screen(&mut commands, &mut cmaterials)
+        .with_children(|parent| {
+            spawn_menu_text(parent, font.clone(), "High Scores", 60.0, Color::WHITE);
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    "Loading...",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 25.0,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        ..Default::default()
+                    },
+                ),
+                style: Style {
+                    margin: Rect::all(Val::Px(5.0)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+                .insert(HighScoreTable);
+            spawn_menu_button(parent, &materials, font.clone(), "Back", MenuButton::Back);
+        });
+    load_requests.send(LoadHighScoresRequest(HIGHSCORE_ROWS));
+}
+
+fn highscores_loaded(
+    last_saved: Res<LastSavedScore>,
+    mut loaded: EventReader<HighScoresLoaded>,
+    mut table_query: Query<&mut Text, With<HighScoreTable>>,
+){
+    for highscores in loaded.iter(){
+        for mut text in table_query.iter_mut(){
+            let style = text.sections[0].style.clone();
+            let line = |value: String, color: Color| TextSection{
+                value,
+                style: TextStyle{ color, ..style.clone() },
+            };
+            text.sections = match &highscores.0{
+                Ok(rows) if rows.is_empty() => vec![line("No scores yet".to_string(), Color::WHITE)],
+                Ok(rows) => rows.iter().enumerate().map(|(rank, row)| {
+                    //highlight the score the player just saved
+                    let color = if last_saved.0 == Some(row.id) {
+                        Color::rgb(0.35, 0.75, 0.35)
+                    } else {
+                        Color::WHITE
+                    };
+                    line(format!("{}. {} - {}\n", rank + 1, row.username, row.score), color)
+                }).collect(),
+                Err(error) => vec![line(format!("Could not load the scores:\n{}", error), Color::WHITE)],
+            };
+        }
+    }
+}
+
+fn options_enter(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut cmaterials: ResMut<Assets<ColorMaterial>>,
+    materials: Res<Materials>,
+    name_entry: Res<NameEntryMode>,
+){
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    spawn_menu_screen(&mut commands, &mut cmaterials)
+        .with_children(|parent| {
+            spawn_menu_text(parent, font.clone(), "Options", 60.0, Color::WHITE);
+            spawn_menu_button(parent, &materials, font.clone(), name_entry.label(), MenuButton::NameEntry);
+            spawn_menu_text(parent, font.clone(), "Move: A / D", 30.0, Color::WHITE);
+            spawn_menu_text(parent, font.clone(), "Fire: Space", 30.0, Color::WHITE);
+            spawn_menu_text(parent, font.clone(), "Pause: Esc", 30.0, Color::WHITE);
+            spawn_menu_text(parent, font.clone(), "Menus: Up / Down, Space", 30.0, Color::WHITE);
+            spawn_menu_button(parent, &materials, font.clone(), "Back", MenuButton::Back);
+        });
+}
+
+pub fn menu_exit(
+    mut commands: Commands,
+    query: Query<Entity, With<MenuScreen>>,
+){
+    for entity in query.iter(){
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn menu_music_stop(
+    mut menu_music: ResMut<MenuMusic>,
+){
+    menu_music.0 = false;
+}
+
+fn menu_button_system(
+    materials: Res<Materials>,
+    mut game_state: ResMut<State<GameState>>,
+    mut app_exit: EventWriter<AppExit>,
+    mut name_entry: ResMut<NameEntryMode>,
+    mut turns: ResMut<Turns>,
+    mut interaction_query: Query<
+        (&Interaction, &mut Handle<ColorMaterial>, &MenuButton, &Children),
+        Changed<Interaction>,
+    >,
+    mut text_query: Query<&mut Text>,
+){
+    for (interaction, mut material, button, children) in interaction_query.iter_mut() {
+        match *interaction{
+            Interaction::Clicked => {
+                *material = materials.pressed.clone();
+                match button{
+                    MenuButton::Start => {
+                        turns.players = 1;
+                        game_state.set(GameState::Playing).ok();
+                    }
+                    MenuButton::TwoPlayers => {
+                        turns.players = 2;
+                        game_state.set(GameState::Playing).ok();
+                    }
+                    MenuButton::HighScores => { game_state.set(GameState::HighScores).ok(); }
+                    MenuButton::Options => { game_state.set(GameState::Options).ok(); }
+                    MenuButton::NameEntry => {
+                        *name_entry = name_entry.toggled();
+                        if let Ok(mut text) = text_query.get_mut(children[0]){
+                            text.sections[0].value = name_entry.label().to_string();
+                        }
+                    }
+                    MenuButton::Back => { game_state.set(GameState::MainMenu).ok(); }
+                    MenuButton::Quit => app_exit.send(AppExit),
+                }
+            }
+            Interaction::Hovered => {
+                *material = materials.hovered.clone();
+            }
+            Interaction::None => {
+                *material = materials.normal.clone();
+            }
+        }
+    }
+}
+
+//up/down moves a focus over the buttons of the screen, fire presses the focused one
+fn menu_navigation(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_input: Res<Input<GamepadButton>>,
+    materials: Res<Materials>,
+    mut focus: Local<Option<Entity>>,
+    mut button_query: Query<(Entity, &GlobalTransform, &mut Interaction, &mut Handle<ColorMaterial>), With<MenuButton>>,
+){
+    let key = |codes: &[KeyCode], button: GamepadButtonType| {
+        codes.iter().any(|code| keyboard_input.just_pressed(*code))
+            || gamepad_input.get_just_pressed().any(|pressed| pressed.1 == button)
+    };
+    let up = key(&[KeyCode::Up, KeyCode::W], GamepadButtonType::DPadUp);
+    let down = key(&[KeyCode::Down, KeyCode::S], GamepadButtonType::DPadDown);
+    let confirm = key(&[KeyCode::Space, KeyCode::Return], GamepadButtonType::South);
+    if !(up || down || confirm) {
+        return;
+    }
+
+    //top to bottom, ui y goes up
+    let mut buttons: Vec<(Entity, f32)> = button_query.iter_mut()
+        .map(|(entity, tf, _, _)| (entity, tf.translation.y))
+        .collect();
+    buttons.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+    if buttons.is_empty() {
+        return;
+    }
+
+    let count = buttons.len();
+    //the focus of a screen left already starts over at the top
+    let next = match focus.and_then(|focused| buttons.iter().position(|(entity, _)| *entity == focused)){
+        None => 0,
+        Some(i) if up => (i + count - 1) % count,
+        Some(i) if down => (i + 1) % count,
+        Some(i) => {
+            //menu_button_system takes it from there like a mouse click
+            if let Ok((_, _, mut interaction, _)) = button_query.get_mut(buttons[i].0){
+                *interaction = Interaction::Clicked;
+            }
+            return;
+        }
+    };
+    *focus = Some(buttons[next].0);
+    for (entity, _, _, mut material) in button_query.iter_mut(){
+        *material = if entity == buttons[next].0 { materials.hovered.clone() } else { materials.normal.clone() };
+    }
+}
+
+pub fn spawn_menu_screen<'a, 'b>(
+    commands: &'b mut Commands<'a>,
+    cmaterials: &mut Assets<ColorMaterial>,
+) -> bevy::ecs::system::EntityCommands<'a, 'b> {
+    let mut screen = commands.spawn_bundle(NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            position_type: PositionType::Absolute,
+            // ui goes bottom to top, reverse it so the first child is on top
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        material: cmaterials.add(Color::NONE.into()),
+        ..Default::default()
+    });
+    screen.insert(MenuScreen);
+    screen
+}
+
+pub fn spawn_menu_text(parent: &mut ChildBuilder, font: Handle<Font>, value: &str, font_size: f32, color: Color){
+    parent.spawn_bundle(TextBundle {
+        text: Text::with_section(
+            value,
+            TextStyle {
+                font,
+                font_size,
+                color,
+            },
+            TextAlignment {
+                horizontal: HorizontalAlign::Center,
+                ..Default::default()
+            },
+        ),
+        style: Style {
+            margin: Rect::all(Val::Px(5.0)),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+}
+
+pub fn spawn_menu_button(parent: &mut ChildBuilder, materials: &Materials, font: Handle<Font>, label: &str, button: MenuButton){
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(250.0), Val::Px(50.0)),
+                margin: Rect::all(Val::Px(5.0)),
+                // horizontally center child text
+                justify_content: JustifyContent::Center,
+                // vertically center child text
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.normal.clone(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    label,
+                    TextStyle {
+                        font,
+                        font_size: 30.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        })
+        .insert(button);
+}
@@ -0,0 +1,131 @@
+use bevy::prelude::*;
+use crate::{GameState, PauseState, Speed, TIME_STEP};
+
+pub struct PathPlugin;
+
+impl Plugin for PathPlugin{
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(path_follow.system())
+        );
+    }
+}
+
+//moves the entity along a Catmull-Rom spline through the points at its Speed,
+//stays on the last point once finished so the owner can pick what comes next
+pub struct FollowPath{
+    points: Vec<Vec2>,
+    //distance from the first point to each point, along the straight segments
+    lengths: Vec<f32>,
+    travelled: f32,
+}
+
+impl FollowPath{
+    pub fn new(points: Vec<Vec2>) -> Self {
+        let mut lengths = vec![0.];
+        for pair in points.windows(2){
+            let last = *lengths.last().unwrap();
+            lengths.push(last + pair[0].distance(pair[1]));
+        }
+        FollowPath{
+            points,
+            lengths,
+            travelled: 0.,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.travelled >= *self.lengths.last().unwrap_or(&0.)
+    }
+
+    fn position(&self) -> Vec2 {
+        if self.points.len() < 2 {
+            return self.points.first().cloned().unwrap_or(Vec2::ZERO);
+        }
+        let last = self.points.len() - 1;
+        if self.finished(){
+            return self.points[last];
+        }
+
+        //segment we are on, and how far along it
+        let segment = self.lengths.iter().rposition(|&l| l <= self.travelled).unwrap_or(0).min(last - 1);
+        let length = self.lengths[segment + 1] - self.lengths[segment];
+        let t = if length > 0. { (self.travelled - self.lengths[segment]) / length } else { 1. };
+
+        //the end points are repeated for the first and last segment
+        let p0 = self.points[segment.saturating_sub(1)];
+        let p1 = self.points[segment];
+        let p2 = self.points[segment + 1];
+        let p3 = self.points[(segment + 2).min(last)];
+
+        let t2 = t * t;
+        let t3 = t2 * t;
+        0.5 * (2. * p1
+            + (p2 - p0) * t
+            + (2. * p0 - 5. * p1 + 4. * p2 - p3) * t2
+            + (3. * p1 - p0 - 3. * p2 + p3) * t3)
+    }
+}
+
+fn path_follow(
+    mut query: Query<(&mut Transform, &mut FollowPath, &PauseState, &Speed)>,
+){
+    for (mut tf, mut path, pause, speed) in query.iter_mut(){
+        if !pause.0 && !path.finished(){
+            path.travelled += speed.v * TIME_STEP;
+            let position = path.position();
+            tf.translation.x = position.x;
+            tf.translation.y = position.y;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn at(points: &[(f32, f32)], travelled: f32) -> FollowPath {
+        let mut path = FollowPath::new(points.iter().map(|&(x, y)| Vec2::new(x, y)).collect());
+        path.travelled = travelled;
+        path
+    }
+
+    #[test]
+    fn lengths_along_the_segments(){
+        let path = at(&[(0., 0.), (30., 40.), (30., 0.)], 0.);
+        assert_eq!(path.lengths, vec![0., 50., 90.]);
+    }
+
+    #[test]
+    fn passes_through_the_points(){
+        let points = [(0., 0.), (30., 40.), (30., 0.), (-20., -10.)];
+        assert_eq!(at(&points, 0.).position(), Vec2::new(0., 0.));
+        assert!(at(&points, 50.).position().distance(Vec2::new(30., 40.)) < 1e-4);
+        assert!(at(&points, 90.).position().distance(Vec2::new(30., 0.)) < 1e-4);
+    }
+
+    #[test]
+    fn stays_on_the_last_point(){
+        let path = at(&[(0., 0.), (10., 0.)], 25.);
+        assert!(path.finished());
+        assert_eq!(path.position(), Vec2::new(10., 0.));
+        assert!(!at(&[(0., 0.), (10., 0.)], 5.).finished());
+    }
+
+    #[test]
+    fn without_segments(){
+        assert_eq!(at(&[], 0.).position(), Vec2::ZERO);
+        assert!(at(&[], 0.).finished());
+        assert_eq!(at(&[(3., 4.)], 0.).position(), Vec2::new(3., 4.));
+    }
+
+    #[test]
+    fn repeated_points_stay_finite(){
+        let points = [(0., 0.), (10., 0.), (10., 0.), (20., 0.)];
+        for i in 0..=20{
+            let position = at(&points, i as f32).position();
+            assert!(position.x.is_finite() && position.y.is_finite());
+        }
+    }
+}
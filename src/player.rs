@@ -1,10 +1,22 @@
 use bevy::{core::FixedTimestep, prelude::*};
 
-use crate::{Laser, Materials, Player, PlayerReadyFire, Speed, WinSize, SCALE, TIME_STEP, FromPlayer, PlayerState, PLAYER_RESPAWN_DELAY, PauseState, GameState, LaserSpeed, FIRING_SFX};
+use crate::{Laser, Materials, Player, PlayerReadyFire, Speed, WinSize, SCALE, TIME_STEP, FromPlayer, PlayerState, PLAYER_RESPAWN_DELAY, PauseState, GameState, LaserSpeed, FIRING_SFX, DualFighter, Velocity, Invulnerable, PLAYER_INVULNERABLE_TIME};
+use crate::powerup::{ActivePowerUps, PowerUpKind, FIRE_INTERVAL};
+use crate::scoring::Scoring;
+use crate::stats::RunStats;
 use bevy_inspector_egui::InspectableRegistry;
+use crate::state::COLLISIONS;
+use crate::tractor::TRACTOR_BEAM;
 
 pub struct PlayerPlugin;
 
+//the wing ship of a dual fighter sits one ship width to the right, in sprite pixels
+pub const WING_OFFSET: f32 = 144.;
+//degrees off straight up of the spread shot lasers
+const SPREAD_ANGLE: f32 = 15.;
+//seconds the ship is shown or hidden while blinking
+const BLINK_TIME: f32 = 0.1;
+
 impl Plugin for PlayerPlugin{
     fn build(&self, app: &mut AppBuilder){
 
@@ -14,9 +26,19 @@ impl Plugin for PlayerPlugin{
             "game_setup_actors",
             SystemStage::single(player_spawn.system()),
         )
-            .add_system(player_movement.system())
-            .add_system(player_fire.system())
-            .add_system(laser_movement.system())
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(player_movement.system())
+                    //the wing goes on before a hit or the beam can despawn the player
+                    .with_system(player_dual.system().before(COLLISIONS).before(TRACTOR_BEAM))
+                    .with_system(player_invulnerable.system())
+                    .with_system(player_fire.system())
+                    .with_system(laser_movement.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::HighScoreEntry)
+                    .with_system(player_reset.system())
+            )
             .add_system_set(
                 SystemSet::new()
                     .with_run_criteria(FixedTimestep::step(0.5))
@@ -28,11 +50,11 @@ impl Plugin for PlayerPlugin{
 fn player_spawn(mut commands: Commands,
                 win_size: Res<WinSize>,
                 materials: Res<Materials>,
-                game_state: Res<GameState>,
+                game_state: Res<State<GameState>>,
                 time: Res<Time>,
                 mut player_state: ResMut<PlayerState>,)
 {
-    if game_state.0 != "gameover".to_string(){
+    if *game_state.current() == GameState::Playing{
         let now = time.seconds_since_startup();
         let last_shot = player_state.last_shot;
 
@@ -54,26 +76,87 @@ fn player_spawn(mut commands: Commands,
                 .insert(PlayerReadyFire(true))
                 .insert(Speed::default())
                 .insert(LaserSpeed::default())
-                .insert(Timer::from_seconds(0.5, true))
-                .insert(if game_state.0 == "active" {PauseState::default()} else {PauseState(true)});
+                .insert(Timer::from_seconds(FIRE_INTERVAL, true))
+                .insert(Invulnerable(Timer::from_seconds(PLAYER_INVULNERABLE_TIME, false)))
+                .insert(PauseState::default());
             player_state.spawned();
         }
     }
 
 }
 
+fn player_reset(
+    mut commands: Commands,
+    mut player_state: ResMut<PlayerState>,
+    query: Query<Entity, Or<(With<Player>, With<FromPlayer>)>>,
+){
+    for entity in query.iter(){
+        commands.entity(entity).despawn_recursive();
+    }
+    *player_state = PlayerState::default();
+}
+
+//gives the player the rescued ship as a wing
+fn player_dual(
+    mut commands: Commands,
+    materials: Res<Materials>,
+    player_state: Res<PlayerState>,
+    query: Query<Entity, (With<Player>, Without<DualFighter>)>,
+){
+    if player_state.dual {
+        if let Ok(player_entity) = query.single(){
+            commands.entity(player_entity)
+                .insert(DualFighter)
+                .with_children(|parent| {
+                    parent.spawn_bundle(SpriteBundle{
+                        material: materials.player.clone(),
+                        transform: Transform::from_xyz(WING_OFFSET, 0., 0.),
+                        ..Default::default()
+                    });
+                });
+        }
+    }
+}
+
+//blinks the ship and its wing while invulnerable
+fn player_invulnerable(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut player_query: Query<(Entity, &mut Invulnerable, &mut Visible, Option<&Children>), With<Player>>,
+    mut children_query: Query<&mut Visible, Without<Player>>,
+){
+    for (entity, mut invulnerable, mut visible, children) in player_query.iter_mut(){
+        invulnerable.0.tick(time.delta());
+        let finished = invulnerable.0.finished();
+        let shown = finished || (invulnerable.0.elapsed_secs() / BLINK_TIME) as u32 % 2 == 0;
+
+        visible.is_visible = shown;
+        if let Some(children) = children {
+            for child in children.iter(){
+                if let Ok(mut child_visible) = children_query.get_mut(*child){
+                    child_visible.is_visible = shown;
+                }
+            }
+        }
+        if finished {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
+
 fn player_movement(
     keyboard_input: Res<Input<KeyCode>>,
     win_size: Res<WinSize>,
-    mut query: Query<(&PauseState, &Speed, &mut Transform, With<Player>)>
+    mut query: Query<(&PauseState, &Speed, &mut Transform, Option<&DualFighter>, With<Player>)>
 ){
 
-    if let Ok((pause, speed, mut transform, _)) = query.single_mut(){
+    if let Ok((pause, speed, mut transform, dual, _)) = query.single_mut(){
 
         if !pause.0{
+            let right = if dual.is_some() { 75.0 + WING_OFFSET * SCALE } else { 75.0 };
             let dir = if keyboard_input.pressed(KeyCode::A) && transform.translation.x - 75.0 > -win_size.w / 2.{
                 -1.
-            } else if keyboard_input.pressed(KeyCode::D) && transform.translation.x + 75.0 < win_size.w / 2.{
+            } else if keyboard_input.pressed(KeyCode::D) && transform.translation.x + right < win_size.w / 2.{
                 1.
             } else{
                 0.
@@ -90,33 +173,51 @@ fn player_fire(
     audio: Res<Audio>,
     kb: Res<Input<KeyCode>>,
     materials: Res<Materials>,
-    mut query: Query<(&Transform,&PauseState,&LaserSpeed,&mut PlayerReadyFire, &mut Timer, With<Player>)>
+    power_ups: Res<ActivePowerUps>,
+    mut scoring: ResMut<Scoring>,
+    mut stats: ResMut<RunStats>,
+    mut query: Query<(&Transform,&PauseState,&LaserSpeed,&mut PlayerReadyFire, &mut Timer, Option<&DualFighter>, With<Player>)>
 ){
-    if let Ok((player_tf, pause_state,lspeed, mut ready_fire, mut timer, _)) = query.single_mut(){
+    if let Ok((player_tf, pause_state,lspeed, mut ready_fire, mut timer, dual, _)) = query.single_mut(){
         if ready_fire.0 && kb.pressed(KeyCode::Space) && !pause_state.0{
             let music = asset_server.load(FIRING_SFX);
             audio.play(music);
             let x = player_tf.translation.x;
             let y = player_tf.translation.y;
 
-            let mut spawn_lasers = |x_offset: f32|{
+            //angle 0. is straight up
+            let mut fired = 0;
+            let mut spawn_lasers = |x_offset: f32, angle: f32|{
+                fired += 1;
                 commands.spawn_bundle(SpriteBundle{
                     material: materials.player_laser.clone(),
                     transform: Transform{
                         translation: Vec3::new(x + x_offset, y + 15., 0.),
+                        rotation: Quat::from_rotation_z(angle),
                         ..Default::default()
                     },
                     ..Default::default()
                 })
                     .insert(Laser)
                     .insert(FromPlayer)
-                    .insert(Speed{v: lspeed.v})
-                    .insert(PauseState::default());;
+                    .insert(Velocity{v: Vec2::new(-angle.sin(), angle.cos()) * lspeed.v})
+                    .insert(PauseState::default());
 
             };
             let x_offset = 144.0 / 4.0 - 5.0;
-            spawn_lasers(x_offset);
-            spawn_lasers(-x_offset);
+            spawn_lasers(x_offset, 0.);
+            spawn_lasers(-x_offset, 0.);
+            if dual.is_some() {
+                let wing_x = WING_OFFSET * SCALE;
+                spawn_lasers(wing_x + x_offset, 0.);
+                spawn_lasers(wing_x - x_offset, 0.);
+            }
+            if power_ups.is_active(PowerUpKind::SpreadShot) {
+                spawn_lasers(-x_offset, SPREAD_ANGLE.to_radians());
+                spawn_lasers(x_offset, -SPREAD_ANGLE.to_radians());
+            }
+            scoring.fired(fired);
+            stats.shots_fired += fired;
 
             ready_fire.0 = false;
         }
@@ -133,14 +234,14 @@ fn player_fire(
 fn laser_movement(
     mut commands: Commands,
     win_size: Res<WinSize>,
-    mut query: Query<(Entity, &PauseState, &Speed, &mut Transform, (With<Laser>, With<FromPlayer>))>
+    mut query: Query<(Entity, &PauseState, &Velocity, &mut Transform, (With<Laser>, With<FromPlayer>))>
 ){
-    for (laser_entity,pause, speed, mut laser_tf, _) in query.iter_mut(){
+    for (laser_entity,pause, velocity, mut laser_tf, _) in query.iter_mut(){
 
         if !pause.0{
             let translation = &mut laser_tf.translation;
-            translation.y += speed.v * TIME_STEP;
-            if translation.y > win_size.h{
+            *translation += velocity.v.extend(0.) * TIME_STEP;
+            if !win_size.contains(*translation, 50.){
                 commands.entity(laser_entity).despawn();
             }
         }
@@ -0,0 +1,353 @@
+use bevy::prelude::*;
+use bevy::sprite::collide_aabb::collide;
+use rand::{thread_rng, Rng};
+use std::collections::HashMap;
+use std::time::Duration;
+use crate::{WinSize, Player, PlayerState, PlayerReadyFire, GameState, PauseState, Velocity, DualFighter, PowerUpToSpawn, Shield, ShieldOverlay, TIME_STEP};
+use crate::level::{CurrentLevel, Level, PowerUpSettings, ExtraLives};
+use crate::state::{player_hitbox, COLLISIONS};
+use crate::tractor::TRACTOR_BEAM;
+use crate::player::WING_OFFSET;
+
+pub struct PowerUpPlugin;
+
+impl Plugin for PowerUpPlugin{
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(ActivePowerUps::default())
+            .add_system(powerup_to_spawn.system())
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(powerup_movement.system())
+                    //the shield goes on the player before a hit can despawn it
+                    .with_system(powerup_pickup.system().before(COLLISIONS).before(TRACTOR_BEAM))
+                    .with_system(powerup_tick.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver)
+                    .with_system(powerup_despawn.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::HighScoreEntry)
+                    .with_system(powerup_reset.system())
+            );
+    }
+}
+
+//seconds between two shots while holding fire
+pub const FIRE_INTERVAL: f32 = 0.5;
+const RAPID_FIRE_INTERVAL: f32 = 0.2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PowerUpKind{
+    //shorter time between two shots
+    RapidFire,
+    //two more lasers fanning out
+    SpreadShot,
+    //takes a few hits instead of the player, not timed
+    Shield,
+    //right away, not timed
+    ExtraLife,
+    ScoreMultiplier,
+}
+
+const POWERUP_KINDS: [PowerUpKind; 5] = [
+    PowerUpKind::RapidFire,
+    PowerUpKind::SpreadShot,
+    PowerUpKind::Shield,
+    PowerUpKind::ExtraLife,
+    PowerUpKind::ScoreMultiplier,
+];
+
+impl PowerUpKind{
+    //on the pickup
+    fn letter(&self) -> &'static str {
+        match self{
+            PowerUpKind::RapidFire => "R",
+            PowerUpKind::SpreadShot => "S",
+            PowerUpKind::Shield => "H",
+            PowerUpKind::ExtraLife => "1",
+            PowerUpKind::ScoreMultiplier => "x",
+        }
+    }
+
+    //in the hud
+    pub fn label(&self) -> &'static str {
+        match self{
+            PowerUpKind::RapidFire => "Rapid fire",
+            PowerUpKind::SpreadShot => "Spread shot",
+            PowerUpKind::Shield => "Shield",
+            PowerUpKind::ExtraLife => "Extra life",
+            PowerUpKind::ScoreMultiplier => "Score multiplier",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self{
+            PowerUpKind::RapidFire => Color::rgb(0.95, 0.85, 0.2),
+            PowerUpKind::SpreadShot => Color::rgb(0.95, 0.55, 0.15),
+            PowerUpKind::Shield => Color::rgb(0.3, 0.8, 0.95),
+            PowerUpKind::ExtraLife => Color::rgb(0.35, 0.75, 0.35),
+            PowerUpKind::ScoreMultiplier => Color::rgb(0.8, 0.4, 0.9),
+        }
+    }
+}
+
+//the timed power-ups the player has and the time they have left
+#[derive(Default)]
+pub struct ActivePowerUps{
+    timers: HashMap<PowerUpKind, Timer>,
+    score_multiplier: u32,
+}
+
+impl ActivePowerUps{
+    pub fn is_active(&self, kind: PowerUpKind) -> bool {
+        self.timers.contains_key(&kind)
+    }
+
+    //the timed ones and their seconds left, always in the same order
+    pub fn remaining(&self) -> Vec<(PowerUpKind, f32)> {
+        let mut remaining: Vec<(PowerUpKind, f32)> = self.timers.iter()
+            .map(|(kind, timer)| (*kind, timer.duration().as_secs_f32() - timer.elapsed_secs()))
+            .collect();
+        remaining.sort_by_key(|(kind, _)| POWERUP_KINDS.iter().position(|k| k == kind));
+        remaining
+    }
+
+    //what a kill is multiplied with
+    pub fn score_factor(&self) -> u32 {
+        if self.is_active(PowerUpKind::ScoreMultiplier) { self.score_multiplier.max(1) } else { 1 }
+    }
+
+    //picking one up again starts its time over
+    fn start(&mut self, kind: PowerUpKind, duration: f32, score_multiplier: u32){
+        if kind == PowerUpKind::ScoreMultiplier {
+            self.score_multiplier = score_multiplier;
+        }
+        self.timers.insert(kind, Timer::from_seconds(duration, false));
+    }
+
+    //drops the ones whose time ran out
+    fn tick(&mut self, delta: Duration){
+        for timer in self.timers.values_mut(){
+            timer.tick(delta);
+        }
+        self.timers.retain(|_, timer| !timer.finished());
+    }
+}
+
+struct PowerUp(PowerUpKind);
+
+//rolls the drop chance of the level for every destroyed enemy
+fn powerup_to_spawn(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    levels: Res<Assets<Level>>,
+    current_level: Res<CurrentLevel>,
+    query: Query<(Entity, &PowerUpToSpawn)>,
+){
+    let settings = current_level.get(&levels, &asset_server)
+        .map(|level| level.power_ups.clone())
+        .unwrap_or_default();
+    let mut rng = thread_rng();
+
+    for (entity, to_spawn) in query.iter(){
+        commands.entity(entity).despawn();
+        if rng.gen::<f32>() >= settings.drop_chance {
+            continue;
+        }
+
+        let kind = POWERUP_KINDS[rng.gen_range(0..POWERUP_KINDS.len())];
+        commands
+            .spawn_bundle(SpriteBundle{
+                material: materials.add(kind.color().into()),
+                sprite: Sprite::new(Vec2::new(24., 24.)),
+                transform: Transform::from_translation(Vec3::new(to_spawn.0.x, to_spawn.0.y, 8.)),
+                ..Default::default()
+            })
+            .with_children(|parent| {
+                parent.spawn_bundle(Text2dBundle{
+                    text: Text::with_section(
+                        kind.letter(),
+                        TextStyle{
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 20.0,
+                            color: Color::BLACK,
+                        },
+                        TextAlignment{
+                            vertical: VerticalAlign::Center,
+                            horizontal: HorizontalAlign::Center,
+                        },
+                    ),
+                    transform: Transform::from_xyz(0., 0., 1.),
+                    ..Default::default()
+                });
+            })
+            .insert(PowerUp(kind))
+            .insert(Velocity{v: Vec2::new(0., -settings.fall_speed)})
+            .insert(PauseState::default());
+    }
+}
+
+fn powerup_movement(
+    mut commands: Commands,
+    win_size: Res<WinSize>,
+    mut query: Query<(Entity, &PauseState, &Velocity, &mut Transform), With<PowerUp>>,
+){
+    for (entity, pause, velocity, mut tf) in query.iter_mut(){
+        if !pause.0 {
+            tf.translation += velocity.v.extend(0.) * TIME_STEP;
+            if !win_size.contains(tf.translation, 50.) {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+fn powerup_pickup(
+    mut commands: Commands,
+    mut active: ResMut<ActivePowerUps>,
+    mut player_state: ResMut<PlayerState>,
+    asset_server: Res<AssetServer>,
+    levels: Res<Assets<Level>>,
+    current_level: Res<CurrentLevel>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    powerup_query: Query<(Entity, &Transform, &Sprite, &PowerUp)>,
+    mut player_query: Query<(Entity, &Transform, &Sprite, Option<&DualFighter>, Option<&mut Shield>), With<Player>>,
+){
+    if !player_state.on {
+        return;
+    }
+    if let Ok((player_entity, player_tf, player_sprite, dual, mut shield)) = player_query.single_mut() {
+        let (player_center, player_size) = player_hitbox(player_tf, player_sprite, dual.is_some());
+
+        for (entity, tf, sprite, powerup) in powerup_query.iter(){
+            let collision = collide(
+                tf.translation,
+                sprite.size,
+                player_center,
+                player_size,
+            );
+            if collision.is_none() {
+                continue;
+            }
+            commands.entity(entity).despawn_recursive();
+
+            let (settings, max_lives): (PowerUpSettings, u32) = current_level.get(&levels, &asset_server)
+                .map(|level| (level.power_ups.clone(), level.extra_lives.max_lives))
+                .unwrap_or_else(|| (PowerUpSettings::default(), ExtraLives::default().max_lives));
+            match powerup.0{
+                PowerUpKind::ExtraLife => {
+                    //wasted on a full stock of lives
+                    if player_state.lifes < max_lives {
+                        player_state.lifes += 1;
+                    }
+                }
+                PowerUpKind::Shield => {
+                    match shield.as_deref_mut(){
+                        //a second one tops the hits up again
+                        Some(shield) => shield.hits = settings.shield_hits,
+                        None => {
+                            //in sprite pixels of the ship, around the wing too
+                            let (width, x) = if dual.is_some() { (WING_OFFSET + 180., WING_OFFSET / 2.) } else { (180., 0.) };
+                            let overlay = commands
+                                .spawn_bundle(SpriteBundle{
+                                    material: materials.add(Color::rgba(0.3, 0.8, 0.95, 0.3).into()),
+                                    sprite: Sprite::new(Vec2::new(width, 110.)),
+                                    transform: Transform::from_xyz(x, 0., 1.),
+                                    ..Default::default()
+                                })
+                                .insert(ShieldOverlay)
+                                .id();
+                            commands.entity(player_entity)
+                                .push_children(&[overlay])
+                                .insert(Shield{
+                                    hits: settings.shield_hits,
+                                    overlay,
+                                });
+                        }
+                    }
+                }
+                kind => active.start(kind, settings.duration, settings.score_multiplier),
+            }
+        }
+    }
+}
+
+fn powerup_tick(
+    time: Res<Time>,
+    mut active: ResMut<ActivePowerUps>,
+    mut fire_query: Query<&mut Timer, (With<Player>, With<PlayerReadyFire>)>,
+){
+    active.tick(time.delta());
+
+    //the player fire timer, see player_fire
+    let interval = if active.is_active(PowerUpKind::RapidFire) { RAPID_FIRE_INTERVAL } else { FIRE_INTERVAL };
+    for mut timer in fire_query.iter_mut(){
+        if timer.duration().as_secs_f32() != interval {
+            timer.set_duration(Duration::from_secs_f32(interval));
+        }
+    }
+}
+
+fn powerup_despawn(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<PowerUp>, With<PowerUpToSpawn>)>>,
+){
+    for entity in query.iter(){
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn powerup_reset(
+    mut active: ResMut<ActivePowerUps>,
+){
+    *active = ActivePowerUps::default();
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn expires_after_its_duration(){
+        let mut active = ActivePowerUps::default();
+        active.start(PowerUpKind::RapidFire, 2., 2);
+        active.tick(Duration::from_secs_f32(1.5));
+        assert!(active.is_active(PowerUpKind::RapidFire));
+        active.tick(Duration::from_secs_f32(0.5));
+        assert!(!active.is_active(PowerUpKind::RapidFire));
+    }
+
+    #[test]
+    fn picking_up_again_restarts_the_time(){
+        let mut active = ActivePowerUps::default();
+        active.start(PowerUpKind::SpreadShot, 2., 2);
+        active.tick(Duration::from_secs_f32(1.5));
+        active.start(PowerUpKind::SpreadShot, 2., 2);
+        active.tick(Duration::from_secs_f32(1.5));
+        assert!(active.is_active(PowerUpKind::SpreadShot));
+        assert_eq!(active.timers.len(), 1);
+    }
+
+    #[test]
+    fn kinds_run_side_by_side(){
+        let mut active = ActivePowerUps::default();
+        active.start(PowerUpKind::RapidFire, 1., 2);
+        active.start(PowerUpKind::ScoreMultiplier, 3., 2);
+        active.tick(Duration::from_secs_f32(2.));
+        assert!(!active.is_active(PowerUpKind::RapidFire));
+        assert!(active.is_active(PowerUpKind::ScoreMultiplier));
+    }
+
+    #[test]
+    fn score_factor_while_active(){
+        let mut active = ActivePowerUps::default();
+        assert_eq!(active.score_factor(), 1);
+        active.start(PowerUpKind::ScoreMultiplier, 1., 3);
+        assert_eq!(active.score_factor(), 3);
+        active.tick(Duration::from_secs_f32(1.));
+        assert_eq!(active.score_factor(), 1);
+    }
+}
@@ -0,0 +1,247 @@
+use bevy::prelude::*;
+use crate::{WinSize, PlayerState, GameState, Wave};
+use crate::level::{CurrentLevel, Level, ScoringSettings};
+use crate::powerup::ActivePowerUps;
+
+pub struct ScoringPlugin;
+
+impl Plugin for ScoringPlugin{
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_event::<EnemyKilled>()
+            .insert_resource(Scoring::default())
+            .add_system(animate_score_popup.system())
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(score_kills.system())
+                    .with_system(combo_tick.system())
+                    .with_system(accuracy_bonus.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::HighScoreEntry)
+                    .with_system(scoring_reset.system())
+            );
+    }
+}
+
+//seconds a popup rises and fades out
+const POPUP_TIME: f32 = 1.;
+const POPUP_RISE_SPEED: f32 = 40.;
+const POPUP_FONT_SIZE: f32 = 18.;
+
+//an enemy the player shot down or crashed into, scored by score_kills
+pub struct EnemyKilled{
+    //name of the enemy type
+    pub enemy_type: String,
+    //of the enemy type
+    pub score: u32,
+    pub translation: Vec3,
+    //it was attacking out of the grid
+    pub diving: bool,
+}
+
+//the combo running and the lasers of the current wave, for its accuracy bonus
+pub struct Scoring{
+    combo: u32,
+    combo_timer: Timer,
+    shots: u32,
+    hits: u32,
+    //the wave the shots and hits are counted for
+    wave: u32,
+}
+
+impl Default for Scoring{
+    fn default() -> Self {
+        Scoring{
+            combo: 0,
+            combo_timer: Timer::from_seconds(ScoringSettings::default().combo_window, false),
+            shots: 0,
+            hits: 0,
+            wave: 0,
+        }
+    }
+}
+
+impl Scoring{
+    //lasers the player just fired
+    pub fn fired(&mut self, lasers: u32){
+        self.shots += lasers;
+    }
+
+    //a player laser hit an enemy, destroyed or not
+    pub fn hit(&mut self){
+        self.hits += 1;
+    }
+
+    //kills in a row so far, 0 once the window passed
+    pub fn combo(&self) -> u32 {
+        self.combo
+    }
+
+    //share of the lasers of this wave that hit, between 0 and 1
+    fn accuracy(&self) -> f32 {
+        if self.shots == 0 { 0. } else { self.hits.min(self.shots) as f32 / self.shots as f32 }
+    }
+
+    fn combo_multiplier(&self, settings: &ScoringSettings) -> u32 {
+        let steps = self.combo.saturating_sub(1) / settings.combo_step.max(1);
+        (1 + steps).min(settings.max_combo_multiplier.max(1))
+    }
+}
+
+struct ScorePopup(Timer);
+
+fn spawn_score_popup(commands: &mut Commands, asset_server: &AssetServer, value: String, translation: Vec3, color: Color){
+    commands
+        .spawn_bundle(Text2dBundle{
+            text: Text::with_section(
+                value,
+                TextStyle{
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: POPUP_FONT_SIZE,
+                    color,
+                },
+                TextAlignment{
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
+                },
+            ),
+            transform: Transform::from_xyz(translation.x, translation.y, 20.),
+            ..Default::default()
+        })
+        .insert(ScorePopup(Timer::from_seconds(POPUP_TIME, false)));
+}
+
+fn scoring_settings(levels: &Assets<Level>, current_level: &CurrentLevel, asset_server: &AssetServer) -> ScoringSettings {
+    current_level.get(levels, asset_server)
+        .map(|level| level.scoring.clone())
+        .unwrap_or_default()
+}
+
+//the score of the enemy type times the dive, combo and power-up multipliers
+fn score_kills(
+    mut commands: Commands,
+    mut killed: EventReader<EnemyKilled>,
+    mut scoring: ResMut<Scoring>,
+    mut player_state: ResMut<PlayerState>,
+    power_ups: Res<ActivePowerUps>,
+    asset_server: Res<AssetServer>,
+    levels: Res<Assets<Level>>,
+    current_level: Res<CurrentLevel>,
+){
+    let settings = scoring_settings(&levels, &current_level, &asset_server);
+
+    for kill in killed.iter(){
+        if scoring.combo_timer.finished() {
+            scoring.combo = 0;
+        }
+        scoring.combo += 1;
+        scoring.combo_timer = Timer::from_seconds(settings.combo_window, false);
+
+        let dive = if kill.diving { settings.dive_multiplier.max(1) } else { 1 };
+        let multiplier = dive * scoring.combo_multiplier(&settings) * power_ups.score_factor();
+        let points = kill.score * multiplier;
+        player_state.score += points;
+
+        //multiplied kills stand out
+        let color = if multiplier > 1 { Color::rgb(0.95, 0.85, 0.2) } else { Color::WHITE };
+        spawn_score_popup(&mut commands, &asset_server, points.to_string(), kill.translation, color);
+    }
+}
+
+fn combo_tick(
+    time: Res<Time>,
+    player_state: Res<PlayerState>,
+    mut scoring: ResMut<Scoring>,
+){
+    scoring.combo_timer.tick(time.delta());
+    //losing a life ends the combo
+    if scoring.combo_timer.finished() || !player_state.on {
+        scoring.combo = 0;
+    }
+}
+
+//a wave was cleared, its accuracy gives a bonus
+fn accuracy_bonus(
+    mut commands: Commands,
+    mut scoring: ResMut<Scoring>,
+    mut player_state: ResMut<PlayerState>,
+    wave: Res<Wave>,
+    win_size: Res<WinSize>,
+    asset_server: Res<AssetServer>,
+    levels: Res<Assets<Level>>,
+    current_level: Res<CurrentLevel>,
+){
+    if wave.number == scoring.wave {
+        return;
+    }
+
+    //nothing cleared before the first wave
+    if scoring.wave > 0 && scoring.shots > 0 {
+        let settings = scoring_settings(&levels, &current_level, &asset_server);
+        let accuracy = scoring.accuracy();
+        let bonus = (settings.accuracy_bonus as f32 * accuracy).round() as u32;
+        player_state.score += bonus;
+        spawn_score_popup(
+            &mut commands,
+            &asset_server,
+            format!("Accuracy {:.0}%  +{}", accuracy * 100., bonus),
+            Vec3::new(0., win_size.h / 8., 0.),
+            Color::rgb(0.3, 0.8, 0.95),
+        );
+    }
+
+    scoring.wave = wave.number;
+    scoring.shots = 0;
+    scoring.hits = 0;
+}
+
+fn animate_score_popup(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut ScorePopup, &mut Transform, &mut Text)>,
+){
+    for (entity, mut popup, mut tf, mut text) in query.iter_mut(){
+        popup.0.tick(time.delta());
+        if popup.0.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        tf.translation.y += POPUP_RISE_SPEED * time.delta_seconds();
+        for section in text.sections.iter_mut(){
+            section.style.color.set_a(1. - popup.0.percent());
+        }
+    }
+}
+
+fn scoring_reset(
+    mut scoring: ResMut<Scoring>,
+){
+    *scoring = Scoring::default();
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn multiplier(combo: u32, settings: &ScoringSettings) -> u32 {
+        Scoring{ combo, ..Scoring::default() }.combo_multiplier(settings)
+    }
+
+    #[test]
+    fn combo_multiplier_steps(){
+        let settings = ScoringSettings::default();
+        let multipliers: Vec<u32> = [0, 1, 4, 5, 8, 9, 13, 100].iter().map(|&combo| multiplier(combo, &settings)).collect();
+        assert_eq!(multipliers, vec![1, 1, 1, 2, 2, 3, 4, 4]);
+    }
+
+    #[test]
+    fn combo_multiplier_zero_settings(){
+        //a level file setting them to 0 neither divides by zero nor takes the score away
+        let settings = ScoringSettings{ combo_step: 0, max_combo_multiplier: 0, ..ScoringSettings::default() };
+        assert_eq!(multiplier(10, &settings), 1);
+
+        let settings = ScoringSettings{ combo_step: 0, ..ScoringSettings::default() };
+        assert_eq!(multiplier(3, &settings), 3);
+    }
+}
@@ -1,42 +1,83 @@
 use bevy::prelude::*;
-use crate::{GameState, PlayerState, Laser, FromPlayer, Enemy, ActiveEnemies, KILL_SFX, ExplosionToSpawn, PauseText, FromEnemy, Player, GameOverToSpawn, GAMEOVER_SFX, DEAD_SFX, Materials, Explosion, GameOverText, PauseState};
+use crate::{GameState, PlayerState, Laser, FromPlayer, Enemy, ActiveEnemies, KILL_SFX, ExplosionToSpawn, PauseText, FromEnemy, Player, DEAD_SFX, Materials, Explosion, GameOverText, PauseState, GameOverTimer, HitPoints, HoldsCapturedShip, Diving, DualFighter, PowerUpToSpawn, Invulnerable, Shield, EXTRA_LIFE_SFX};
+use crate::level::{CurrentLevel, Level};
+use crate::scoring::{Scoring, EnemyKilled};
+use crate::stats::{RunStats, RunSummary};
+use crate::level::EnemyType;
 use std::collections::HashSet;
 use bevy::sprite::collide_aabb::collide;
 
 pub struct StatePlugin;
 
+//the systems that despawn ships on a hit, systems inserting components on ships run before
+//them so the inserts are applied before the despawns
+pub const COLLISIONS: &str = "collisions";
+
+//a score threshold gave the player a life, the hud flashes it
+pub struct ExtraLifeAwarded;
+
 impl Plugin for StatePlugin {
     fn build(&self, app: &mut AppBuilder) {
         app
-            .add_system(player_laser_hit_enemy.system())
-            .add_system(enemy_laser_hit_player.system())
+            .add_event::<ExtraLifeAwarded>()
             .add_system(explosion_to_spawn.system())
             .add_system(animate_explosion.system())
-            .add_system(pause_game.system())
-            .add_system(gameover_to_spawn.system());
+            .add_system(animate_shield_break.system())
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(player_laser_hit_enemy.system().label(COLLISIONS))
+                    .with_system(enemy_laser_hit_player.system().label(COLLISIONS))
+                    .with_system(enemy_hit_player.system().label(COLLISIONS))
+                    .with_system(extra_life_award.system())
+                    .with_system(pause_game.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Paused)
+                    .with_system(pause_enter.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Paused)
+                    .with_system(resume_game.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Paused)
+                    .with_system(pause_exit.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver)
+                    .with_system(gameover_enter.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::GameOver)
+                    .with_system(gameover_delay.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::HighScoreEntry)
+                    .with_system(gameover_exit.system())
+            );
     }
 }
 
 fn player_laser_hit_enemy(
     mut commands: Commands,
-    game_state: Res<GameState>,
     asset_server: Res<AssetServer>,
     audio: Res<Audio>,
     mut player_state: ResMut<PlayerState>,
+    mut scoring: ResMut<Scoring>,
+    mut stats: ResMut<RunStats>,
+    mut killed: EventWriter<EnemyKilled>,
     mut laser_query: Query<(Entity, &Transform, &Sprite,(With<Laser>, With<FromPlayer>))>,
-    mut enemy_query: Query<(Entity, &Transform, &Sprite, With<Enemy>)>,
+    mut enemy_query: Query<(Entity, &Transform, &Sprite, &EnemyType, &mut HitPoints, Option<&HoldsCapturedShip>, Option<&Diving>), With<Enemy>>,
     mut active_enemies: ResMut<ActiveEnemies>,
 ){
     let mut enemies_blasted: HashSet<Entity> = HashSet::new();
-    for (enemy_entity, enemy_tf, enemy_sprite, _) in enemy_query.iter_mut(){
-        if game_state.0 == "gameover"{
-            commands.entity(enemy_entity).despawn();
-            active_enemies.0 = 0;
-        }
-    }
 
     for(laser_entity, laser_tf, laser_sprite, _) in laser_query.iter_mut(){
-        for(enemy_entity, enemy_tf, enemy_sprite, _) in enemy_query.iter_mut(){
+        for(enemy_entity, enemy_tf, enemy_sprite, enemy_type, mut hit_points, captured_ship, diving) in enemy_query.iter_mut(){
+            //already destroyed this frame
+            if hit_points.0 == 0 {
+                continue;
+            }
             let laser_scale = Vec2::from(laser_tf.scale);
             let enemy_scale = Vec2::from(enemy_tf.scale);
             let collision = collide(
@@ -48,27 +89,30 @@ fn player_laser_hit_enemy(
 
             if let Some(_) = collision {
 
-                if enemies_blasted.get(&enemy_entity).is_none() {
-                    // remove the enemy
-                    commands.entity(enemy_entity).despawn();
-                    active_enemies.0 -= 1;
-                    player_state.score += 1;
+                scoring.hit();
+                stats.hits += 1;
+                hit_points.0 = hit_points.0.saturating_sub(1);
+                if hit_points.0 == 0 && enemies_blasted.get(&enemy_entity).is_none() {
+                    kill_enemy(&mut commands, &mut active_enemies, &mut killed, enemy_entity, enemy_tf, enemy_type, &mut hit_points, diving.is_some());
+                    //the captured ship joins the player, it leaves with the boss otherwise
+                    if captured_ship.is_some() {
+                        player_state.dual = true;
+                    }
 
                     let music = asset_server.load(KILL_SFX);
                     audio.play(music);
                     //Audio::play(music, ());
 
-                    //Spawn explosion
                     commands
                         .spawn()
-                        .insert(ExplosionToSpawn(enemy_tf.translation.clone()));
+                        .insert(PowerUpToSpawn(enemy_tf.translation.clone()));
 
                     enemies_blasted.insert(enemy_entity);
                 }
 
-                // remove the laser
+                // remove the laser, it only hits one enemy
                 commands.entity(laser_entity).despawn();
-
+                break;
             }
         }
     }
@@ -77,58 +121,210 @@ fn player_laser_hit_enemy(
 fn enemy_laser_hit_player(
     mut commands: Commands,
     mut player_state: ResMut<PlayerState>,
-    mut game_state: ResMut<GameState>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
     asset_server: Res<AssetServer>,
     audio: Res<Audio>,
-    mut pause_query: Query<(&mut Visible, (With<PauseText>))>,
     time: Res<Time>,
     laser_query: Query<(Entity, &Transform, &Sprite), (With<Laser>, With<FromEnemy>)>,
-    player_query: Query<(Entity, &Transform, &Sprite), With<Player>>,
+    mut player_query: Query<(Entity, &Transform, &Sprite, Option<&DualFighter>, Option<&mut Shield>), (With<Player>, Without<Invulnerable>)>,
+){
+    if player_state.on {
+        if let Ok((player_entity, player_tf, player_sprite, dual, mut shield)) = player_query.single_mut() {
+            let (player_center, player_size) = player_hitbox(player_tf, player_sprite, dual.is_some());
+
+            for (laser_entity, laser_tf, laser_sprite) in laser_query.iter() {
+                let laser_size = laser_sprite.size * Vec2::from(laser_tf.scale.abs());
+
+                let collision = collide(
+                    laser_tf.translation,
+                    laser_size,
+                    player_center,
+                    player_size,
+                );
+
+                if let Some(_) = collision {
+                    commands.entity(laser_entity).despawn();
+                    if shield_hit(&mut commands, &mut materials, player_entity, player_tf, shield.as_deref_mut()) {
+                        continue;
+                    }
+
+                    kill_player(&mut commands, &mut player_state, &asset_server, &audio, time.seconds_since_startup(), player_entity, player_tf);
+
+                    // the player is gone, the remaining lasers can't hit it anymore
+                    break;
+                }
+            }
+        }
+    }
+}
+
+//despawns the enemy and leaves the scoring to score_kills
+fn kill_enemy(
+    commands: &mut Commands,
+    active_enemies: &mut ActiveEnemies,
+    killed: &mut EventWriter<EnemyKilled>,
+    enemy_entity: Entity,
+    enemy_tf: &Transform,
+    enemy_type: &EnemyType,
+    hit_points: &mut HitPoints,
+    diving: bool,
+){
+    //the other collision systems skip it from now on
+    hit_points.0 = 0;
+    commands.entity(enemy_entity).despawn();
+    active_enemies.0 = active_enemies.0.saturating_sub(1);
+    killed.send(EnemyKilled{
+        enemy_type: enemy_type.name.clone(),
+        score: enemy_type.score,
+        translation: enemy_tf.translation,
+        diving,
+    });
+    commands
+        .spawn()
+        .insert(ExplosionToSpawn(enemy_tf.translation.clone()));
+}
+
+//takes a life, what comes after the last one is up to turns.rs
+pub fn kill_player(
+    commands: &mut Commands,
+    player_state: &mut PlayerState,
+    asset_server: &AssetServer,
+    audio: &Audio,
+    time: f64,
+    player_entity: Entity,
+    player_tf: &Transform,
+){
+    commands.entity(player_entity).despawn_recursive();
+    if !player_state.shot_or_dead(time) {
+        audio.play(asset_server.load(DEAD_SFX));
+    }
+    commands
+        .spawn()
+        .insert(ExplosionToSpawn(player_tf.translation.clone()));
+}
+
+//returns true when a shield took the hit, the last hit breaks it
+fn shield_hit(
+    commands: &mut Commands,
+    materials: &mut Assets<ColorMaterial>,
+    player_entity: Entity,
+    player_tf: &Transform,
+    shield: Option<&mut Shield>,
+) -> bool {
+    match shield{
+        Some(shield) if shield.hits > 0 => {
+            shield.hits -= 1;
+            if shield.hits == 0 {
+                commands.entity(shield.overlay).despawn();
+                commands.entity(player_entity).remove::<Shield>();
+                commands
+                    .spawn_bundle(SpriteBundle{
+                        material: materials.add(Color::rgba(0.3, 0.8, 0.95, SHIELD_BREAK_ALPHA).into()),
+                        sprite: Sprite::new(Vec2::new(90., 55.)),
+                        transform: Transform::from_translation(player_tf.translation),
+                        ..Default::default()
+                    })
+                    .insert(ShieldBreak(Timer::from_seconds(SHIELD_BREAK_TIME, false)));
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+
+//center and size, a dual fighter covers both ships
+pub fn player_hitbox(tf: &Transform, sprite: &Sprite, dual: bool) -> (Vec3, Vec2) {
+    let mut size = sprite.size * Vec2::from(tf.scale.abs());
+    let mut center = tf.translation;
+    //the wing ship flies on the right
+    if dual {
+        center.x += size.x / 2.;
+        size.x *= 2.;
+    }
+    (center, size)
+}
+
+fn enemy_hit_player(
+    mut commands: Commands,
+    mut player_state: ResMut<PlayerState>,
+    mut active_enemies: ResMut<ActiveEnemies>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    time: Res<Time>,
+    mut killed: EventWriter<EnemyKilled>,
+    mut enemy_query: Query<(Entity, &Transform, &Sprite, &EnemyType, &mut HitPoints, Option<&Diving>), With<Enemy>>,
+    mut player_query: Query<(Entity, &Transform, &Sprite, Option<&DualFighter>, Option<&mut Shield>), (With<Player>, Without<Invulnerable>)>,
 ){
+    if player_state.on {
+        if let Ok((player_entity, player_tf, player_sprite, dual, mut shield)) = player_query.single_mut() {
+            let (player_center, player_size) = player_hitbox(player_tf, player_sprite, dual.is_some());
 
+            for (enemy_entity, enemy_tf, enemy_sprite, enemy_type, mut hit_points, diving) in enemy_query.iter_mut() {
+                //already destroyed this frame
+                if hit_points.0 == 0 {
+                    continue;
+                }
+                let enemy_size = enemy_sprite.size * Vec2::from(enemy_tf.scale.abs());
 
-    for(mut visibility, _) in pause_query.iter_mut() {
-        if player_state.on && !visibility.is_visible {
-            player_state.invurnerable_timer.tick(time.delta());
-            if player_state.invurnerable_timer.finished() {
-                if let Ok((player_entity, player_tf, player_sprite)) = player_query.single() {
-                    let player_size = player_sprite.size * Vec2::from(player_tf.scale.abs());
-
-                    for (laser_entity, laser_tf, laser_sprite) in laser_query.iter() {
-                        let laser_size = laser_sprite.size * Vec2::from(laser_tf.scale.abs());
-
-                        let collision = collide(
-                            laser_tf.translation,
-                            laser_size,
-                            player_tf.translation,
-                            player_size,
-                        );
-
-                        if let Some(_) = collision {
-                            commands.entity(player_entity).despawn();
-                            if player_state.shot_or_dead(time.seconds_since_startup()) {
-                                commands
-                                    .spawn()
-                                    .insert(GameOverToSpawn);
-
-                                game_state.0 = "gameover".to_string();
-                            }
-                            else{
-                                let music = asset_server.load(DEAD_SFX);
-                                audio.play(music);
-                            }
-                            commands.entity(laser_entity).despawn();
-
-                            commands
-                                .spawn()
-                                .insert(ExplosionToSpawn(player_tf.translation.clone()));
-                        }
+                let collision = collide(
+                    enemy_tf.translation,
+                    enemy_size,
+                    player_center,
+                    player_size,
+                );
+
+                if let Some(_) = collision {
+                    //a crash destroys the enemy whatever its hit points
+                    kill_enemy(&mut commands, &mut active_enemies, &mut killed, enemy_entity, enemy_tf, enemy_type, &mut hit_points, diving.is_some());
+
+                    //the shield takes the crash, the enemy is gone anyway
+                    if shield_hit(&mut commands, &mut materials, player_entity, player_tf, shield.as_deref_mut()) {
+                        continue;
                     }
+
+                    kill_player(&mut commands, &mut player_state, &asset_server, &audio, time.seconds_since_startup(), player_entity, player_tf);
+
+                    // the player is gone, the other enemies can't hit it anymore
+                    break;
                 }
             }
         }
     }
 }
+
+//a life for every threshold of the level the score went past, up to its max lives
+fn extra_life_award(
+    mut player_state: ResMut<PlayerState>,
+    mut awarded: EventWriter<ExtraLifeAwarded>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    levels: Res<Assets<Level>>,
+    current_level: Res<CurrentLevel>,
+){
+    if !player_state.is_changed() {
+        return;
+    }
+    let extra_lives = match current_level.get(&levels, &asset_server){
+        Some(level) => level.extra_lives.clone(),
+        None => return,
+    };
+
+    while let Some(threshold) = extra_lives.threshold(player_state.extra_lives_awarded) {
+        if player_state.score < threshold {
+            break;
+        }
+        //passed anyway, a full stock of lives doesn't keep it for later
+        player_state.extra_lives_awarded += 1;
+        if player_state.lifes < extra_lives.max_lives {
+            player_state.lifes += 1;
+            audio.play(asset_server.load(EXTRA_LIFE_SFX));
+            awarded.send(ExtraLifeAwarded);
+        }
+    }
+}
+
 fn explosion_to_spawn(
     mut commands: Commands,
     query: Query<(Entity, &ExplosionToSpawn)>,
@@ -180,110 +376,186 @@ fn animate_explosion(
     }
 }
 
-fn gameover_to_spawn(
+//the flash of a breaking shield, it grows and fades out
+struct ShieldBreak(Timer);
+
+const SHIELD_BREAK_TIME: f32 = 0.3;
+const SHIELD_BREAK_ALPHA: f32 = 0.6;
+
+fn animate_shield_break(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut query: Query<(Entity, &mut ShieldBreak, &mut Transform, &Handle<ColorMaterial>)>,
+){
+    for (entity, mut shield_break, mut tf, material) in query.iter_mut(){
+        shield_break.0.tick(time.delta());
+        let t = shield_break.0.percent();
+        tf.scale = Vec3::splat(1. + t);
+        if let Some(material) = materials.get_mut(material){
+            material.color.set_a(SHIELD_BREAK_ALPHA * (1. - t));
+        }
+        if shield_break.0.finished(){
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn gameover_enter(
     mut commands: Commands,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    query: Query<(Entity, &GameOverToSpawn)>,
     asset_server: Res<AssetServer>,
-    mut game_state: ResMut<GameState>,
+    mut active_enemies: ResMut<ActiveEnemies>,
+    mut gameover_timer: ResMut<GameOverTimer>,
+    enemy_query: Query<Entity, With<Enemy>>,
 ){
+    for enemy_entity in enemy_query.iter(){
+        commands.entity(enemy_entity).despawn();
+    }
+    active_enemies.0 = 0;
+    gameover_timer.0.reset();
 
-    for (gameover_spawn_entity, gameover_to_spawn) in query.iter() {
-        commands
-            .spawn_bundle(NodeBundle {
+    commands
+        .spawn_bundle(NodeBundle {
+            visible: Visible {
+                is_visible: true,
+                is_transparent: false,
+            },
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::FlexEnd,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
                 visible: Visible {
                     is_visible: true,
                     is_transparent: false,
                 },
+                text: Text::with_section(
+                    // Accepts a `String` or any type that converts into a `String`, such as `&str`
+                    "Game Over",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 100.0,
+                        color: Color::WHITE,
+                    },
+                    // Note: You can use `Default::default()` in place of the `TextAlignment`
+                    TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        ..Default::default()
+                    },
+                ),
                 style: Style {
-                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
-                    position_type: PositionType::Absolute,
-                    justify_content: JustifyContent::Center,
-                    align_items: AlignItems::FlexEnd,
+                    align_self: AlignSelf::Center,
                     ..Default::default()
                 },
-                material: materials.add(Color::NONE.into()),
                 ..Default::default()
             })
-            .with_children(|parent| {
-                parent.spawn_bundle(TextBundle {
-                    visible: Visible {
-                        is_visible: true,
-                        is_transparent: false,
-                    },
+                .insert(GameOverText);
+            //filled in from the RunStats, see stats.rs
+            parent
+                .spawn_bundle(TextBundle{
                     text: Text::with_section(
-                        // Accepts a `String` or any type that converts into a `String`, such as `&str`
-                        "Game Over",
-                        TextStyle {
+                        "",
+                        TextStyle{
                             font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                            font_size: 100.0,
-                            color: Color::WHITE,
+                            font_size: 20.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
                         },
-                        // Note: You can use `Default::default()` in place of the `TextAlignment`
-                        TextAlignment {
-                            horizontal: HorizontalAlign::Center,
+                        Default::default(),
+                    ),
+                    style: Style{
+                        position_type: PositionType::Absolute,
+                        position: Rect{
+                            left: Val::Px(10.0),
+                            top: Val::Px(50.0),
                             ..Default::default()
                         },
-                    ),
-                    style: Style {
-                        align_self: AlignSelf::Center,
                         ..Default::default()
                     },
                     ..Default::default()
                 })
-                    .insert(GameOverText);
-            });
-        commands.entity(gameover_spawn_entity).despawn();
+                .insert(RunSummary);
+        });
+}
+
+fn gameover_delay(
+    time: Res<Time>,
+    mut gameover_timer: ResMut<GameOverTimer>,
+    mut game_state: ResMut<State<GameState>>,
+){
+    gameover_timer.0.tick(time.delta());
+    //tried again next frame if another transition is queued already
+    if gameover_timer.0.finished(){
+        game_state.set(GameState::HighScoreEntry).ok();
     }
 }
-//take this with a grain of salt
+
+fn gameover_exit(
+    mut commands: Commands,
+    gameover_query: Query<&Parent, With<GameOverText>>,
+    explosion_query: Query<Entity, Or<(With<Explosion>, With<ExplosionToSpawn>)>>,
+){
+    //despawn the whole node holding the game over text
+    for parent in gameover_query.iter(){
+        commands.entity(parent.0).despawn_recursive();
+    }
+    for entity in explosion_query.iter(){
+        commands.entity(entity).despawn();
+    }
+}
+
 fn pause_game(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut pause_query: Query<(&mut Visible, (With<PauseText>))>,
-    mut pause_state_query: QuerySet<(
-        Query<&mut PauseState, With<Player>>,
-        Query<&mut PauseState,(With<Laser>, With<FromPlayer>)>,
-        Query<&mut PauseState, With<Enemy>>,
-        Query<&mut PauseState, (With<Laser>, With<FromEnemy>)>,
-    )>,
-    mut game_state: ResMut<GameState>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut game_state: ResMut<State<GameState>>,
 ){
-    for(mut visibility, _) in pause_query.iter_mut() {
-        if game_state.0 != "gameover"{
-            if keyboard_input.just_pressed(KeyCode::Escape) {
-                visibility.is_visible = !visibility.is_visible;
-                if(visibility.is_visible){
-                    if let Ok((mut pause)) = pause_state_query.q0_mut().single_mut() {
-                        pause.0 = true;
-                    }
-                    for mut pause in pause_state_query.q1_mut().iter_mut(){
-                        pause.0 = true;
-                    }
-                    for mut pause in pause_state_query.q2_mut().iter_mut(){
-                        pause.0 = true;
-                    }
-                    for mut pause in pause_state_query.q3_mut().iter_mut(){
-                        pause.0 = true;
-                    }
-                    game_state.0 = "pause".to_string();
-                }
-                else{
-                    if let Ok((mut pause)) = pause_state_query.q0_mut().single_mut() {
-                        pause.0 = false;
-                    }
-                    for mut pause in pause_state_query.q1_mut().iter_mut(){
-                        pause.0 = false;
-                    }
-                    for mut pause in pause_state_query.q2_mut().iter_mut(){
-                        pause.0 = false;
-                    }
-                    for mut pause in pause_state_query.q3_mut().iter_mut(){
-                        pause.0 = false;
-                    }
-                    game_state.0 = "active".to_string();
-                }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        // the new state updates in the same frame, don't let it see the key press again
+        keyboard_input.reset(KeyCode::Escape);
+        //a game over queued this frame goes first, no pause then
+        game_state.push(GameState::Paused).ok();
+    }
+}
 
-            }
-        }
+fn resume_game(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut game_state: ResMut<State<GameState>>,
+){
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.reset(KeyCode::Escape);
+        game_state.pop().ok();
     }
-}
\ No newline at end of file
+}
+
+fn pause_enter(
+    mut pause_query: Query<&mut Visible, With<PauseText>>,
+    mut pause_state_query: Query<&mut PauseState>,
+){
+    set_paused(true, &mut pause_query, &mut pause_state_query);
+}
+
+fn pause_exit(
+    mut pause_query: Query<&mut Visible, With<PauseText>>,
+    mut pause_state_query: Query<&mut PauseState>,
+){
+    set_paused(false, &mut pause_query, &mut pause_state_query);
+}
+
+fn set_paused(
+    paused: bool,
+    pause_query: &mut Query<&mut Visible, With<PauseText>>,
+    pause_state_query: &mut Query<&mut PauseState>,
+){
+    for mut visibility in pause_query.iter_mut() {
+        visibility.is_visible = paused;
+    }
+    for mut pause in pause_state_query.iter_mut(){
+        pause.0 = paused;
+    }
+}
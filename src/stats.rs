@@ -0,0 +1,158 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use crate::{PlayerState, GameState, Wave};
+use crate::scoring::{Scoring, EnemyKilled};
+use crate::turns::Turns;
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin{
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(RunStats::default())
+            //deaths that end the run happen on the way out of Playing
+            .add_system(stats_deaths.system())
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(stats_track.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::GameOver)
+                    .with_system(run_summary.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::HighScoreEntry)
+                    .with_system(run_summary.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::HighScoreEntry)
+                    .with_system(stats_reset.system())
+            );
+    }
+}
+
+//what happened during a run, shown when it ends and saved with the score
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct RunStats{
+    //lasers, a spread shot is several
+    pub shots_fired: u32,
+    pub hits: u32,
+    //by enemy type name
+    pub kills: BTreeMap<String, u32>,
+    pub deaths: u32,
+    pub waves_cleared: u32,
+    //seconds in Playing, pauses don't count
+    pub time_survived: f32,
+    pub highest_combo: u32,
+}
+
+impl RunStats{
+    //share of the lasers that hit, a laser is gone after its first hit
+    pub fn accuracy(&self) -> f32 {
+        if self.shots_fired == 0 { 0. } else { self.hits as f32 / self.shots_fired as f32 }
+    }
+
+    pub fn enemies_killed(&self) -> u32 {
+        self.kills.values().sum()
+    }
+}
+
+//the text of the summary panel on the game over screen
+pub struct RunSummary;
+
+fn stats_track(
+    time: Res<Time>,
+    wave: Res<Wave>,
+    scoring: Res<Scoring>,
+    mut killed: EventReader<EnemyKilled>,
+    mut stats: ResMut<RunStats>,
+){
+    stats.time_survived += time.delta_seconds();
+    //the wave being played isn't cleared yet
+    stats.waves_cleared = wave.number.saturating_sub(1);
+    for kill in killed.iter(){
+        *stats.kills.entry(kill.enemy_type.clone()).or_insert(0) += 1;
+    }
+    stats.highest_combo = stats.highest_combo.max(scoring.combo());
+}
+
+//counts the player going from on to off, see PlayerState::shot_or_dead
+fn stats_deaths(
+    mut was_on: Local<bool>,
+    player_state: Res<PlayerState>,
+    mut stats: ResMut<RunStats>,
+){
+    if *was_on && !player_state.on {
+        stats.deaths += 1;
+    }
+    *was_on = player_state.on;
+}
+
+fn run_summary(
+    stats: Res<RunStats>,
+    turns: Res<Turns>,
+    player_state: Res<PlayerState>,
+    wave: Res<Wave>,
+    mut query: Query<&mut Text, With<RunSummary>>,
+){
+    let mut lines = Vec::new();
+    //both players, then the stats of the better one
+    if turns.two_players() {
+        for (player, score, wave) in turns.results(&player_state, &wave){
+            lines.push(format!("Player {}  {}  wave {}", player, score, wave));
+        }
+        lines.push(String::new());
+    }
+
+    let seconds = stats.time_survived as u32;
+    lines.extend(vec![
+        format!("Time survived {}:{:02}", seconds / 60, seconds % 60),
+        format!("Waves cleared {}", stats.waves_cleared),
+        format!("Deaths {}", stats.deaths),
+        format!("Shots fired {}", stats.shots_fired),
+        format!("Hits {}", stats.hits),
+        format!("Accuracy {:.0}%", stats.accuracy() * 100.),
+        format!("Highest combo {}", stats.highest_combo),
+        format!("Enemies killed {}", stats.enemies_killed()),
+    ]);
+    for (enemy_type, kills) in stats.kills.iter(){
+        lines.push(format!("  {} {}", enemy_type, kills));
+    }
+
+    for mut text in query.iter_mut(){
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+fn stats_reset(
+    mut stats: ResMut<RunStats>,
+){
+    *stats = RunStats::default();
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn stats(shots_fired: u32, hits: u32) -> RunStats {
+        RunStats{ shots_fired, hits, ..RunStats::default() }
+    }
+
+    #[test]
+    fn accuracy_share_of_hits(){
+        assert_eq!(stats(0, 0).accuracy(), 0.);
+        assert_eq!(stats(4, 0).accuracy(), 0.);
+        assert_eq!(stats(4, 1).accuracy(), 0.25);
+        assert_eq!(stats(4, 4).accuracy(), 1.);
+    }
+
+    #[test]
+    fn enemies_killed_over_all_types(){
+        let mut run = RunStats::default();
+        assert_eq!(run.enemies_killed(), 0);
+        run.kills.insert("grunt".to_string(), 3);
+        run.kills.insert("boss".to_string(), 1);
+        assert_eq!(run.enemies_killed(), 4);
+    }
+}
@@ -0,0 +1,304 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use bevy::log::error;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use sqlx::mysql::{MySqlPool, MySqlPoolOptions};
+use sqlx::migrate::Migrator;
+use crate::stats::RunStats;
+
+//versioned schema changes, applied on startup
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+//the game version stored with every score
+pub const GAME_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const ALREADY_SAVED: &str = "the score of this run is already saved";
+
+#[derive(Clone)]
+pub struct ScoreRow{
+    pub id: u64,
+    pub username: String,
+    pub score: u32,
+}
+
+#[derive(Clone)]
+pub struct NewScore{
+    pub username: String,
+    pub score: u32,
+    pub game_mode: String,
+    pub wave: u32,
+    pub run_id: String,
+    pub stats: RunStats,
+}
+
+//futures are 'static so they can be moved onto the task pool
+pub trait ScoreStore: Send + Sync{
+    //create or upgrade whatever the store keeps its scores in
+    fn migrate(&self) -> BoxFuture<'static, Result<(), String>>;
+    //returns the id of the saved row
+    fn save(&self, score: NewScore) -> BoxFuture<'static, Result<u64, String>>;
+    //best scores first
+    fn load_top(&self, limit: u32) -> BoxFuture<'static, Result<Vec<ScoreRow>, String>>;
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScoreBackend{
+    MySql,
+    Local,
+}
+
+impl ScoreBackend{
+    //a database url that doesn't parse falls back to the local file
+    pub fn open(&self, database_url: &str) -> Arc<dyn ScoreStore> {
+        match self{
+            ScoreBackend::MySql => match MySqlStore::new(database_url){
+                Ok(store) => Arc::new(store),
+                Err(e) => {
+                    error!("invalid database url, keeping scores in a local file instead: {}", e);
+                    Arc::new(LocalStore::new(LocalStore::default_path()))
+                }
+            },
+            ScoreBackend::Local => Arc::new(LocalStore::new(LocalStore::default_path())),
+        }
+    }
+}
+
+pub struct MySqlStore{
+    pool: MySqlPool,
+}
+
+impl MySqlStore{
+    pub fn new(url: &str) -> Result<Self, String> {
+        // connect_lazy doesn't touch the network, the first query opens the connection
+        let pool = MySqlPoolOptions::new()
+            .max_connections(5)
+            .connect_lazy(url)
+            .map_err(|e| e.to_string())?;
+        Ok(MySqlStore{ pool })
+    }
+}
+
+impl ScoreStore for MySqlStore{
+    fn migrate(&self) -> BoxFuture<'static, Result<(), String>> {
+        let pool = self.pool.clone();
+        async move {
+            MIGRATOR.run(&pool).await.map_err(|e| e.to_string())
+        }.boxed()
+    }
+
+    fn save(&self, score: NewScore) -> BoxFuture<'static, Result<u64, String>> {
+        let pool = self.pool.clone();
+        async move {
+            //the stats rows go in with the score or not at all
+            let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+            let done = sqlx::query("INSERT INTO score (Username, Score, GameMode, WaveReached, GameVersion, RunID) VALUES ( ?, ?, ?, ?, ?, ? )")
+                .bind(score.username)
+                .bind(score.score)
+                .bind(score.game_mode)
+                .bind(score.wave)
+                .bind(GAME_VERSION)
+                .bind(score.run_id)
+                .execute(&mut tx)
+                .await
+                .map_err(|e| match e{
+                    //RunID is unique
+                    sqlx::Error::Database(ref db) if db.code().as_deref() == Some("23000") => ALREADY_SAVED.to_string(),
+                    e => e.to_string(),
+                })?;
+            let id = done.last_insert_id();
+
+            let stats = score.stats;
+            sqlx::query("INSERT INTO score_stats (ScoreID, ShotsFired, Hits, Deaths, WavesCleared, TimeSurvived, HighestCombo) VALUES ( ?, ?, ?, ?, ?, ?, ? )")
+                .bind(id)
+                .bind(stats.shots_fired)
+                .bind(stats.hits)
+                .bind(stats.deaths)
+                .bind(stats.waves_cleared)
+                .bind(stats.time_survived)
+                .bind(stats.highest_combo)
+                .execute(&mut tx)
+                .await
+                .map_err(|e| e.to_string())?;
+            for (enemy_type, kills) in stats.kills{
+                sqlx::query("INSERT INTO score_kills (ScoreID, EnemyType, Kills) VALUES ( ?, ?, ? )")
+                    .bind(id)
+                    .bind(enemy_type)
+                    .bind(kills)
+                    .execute(&mut tx)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+
+            tx.commit().await.map_err(|e| e.to_string())?;
+            Ok(id)
+        }.boxed()
+    }
+
+    fn load_top(&self, limit: u32) -> BoxFuture<'static, Result<Vec<ScoreRow>, String>> {
+        let pool = self.pool.clone();
+        async move {
+            let rows: Vec<(i32, String, i32)> = sqlx::query_as("SELECT UserID, Username, Score FROM score ORDER BY Score DESC, UserID ASC LIMIT ?")
+                .bind(limit)
+                .fetch_all(&pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(rows.into_iter().map(|(id, username, score)| ScoreRow{
+                id: id as u64,
+                username,
+                score: score.max(0) as u32,
+            }).collect())
+        }.boxed()
+    }
+}
+
+//a row of the local score file, mirrors the score table
+#[derive(Serialize, Deserialize)]
+struct StoredScore{
+    id: u64,
+    username: String,
+    score: u32,
+    //seconds since the unix epoch
+    #[serde(default)]
+    created_at: u64,
+    #[serde(default)]
+    game_mode: String,
+    #[serde(default)]
+    wave: u32,
+    #[serde(default)]
+    game_version: String,
+    #[serde(default)]
+    run_id: Option<String>,
+    #[serde(default)]
+    stats: Option<RunStats>,
+}
+
+//scores kept in a ron file, for playing without a database server
+pub struct LocalStore{
+    path: PathBuf,
+    //saves read and rewrite the whole file, don't let two of them interleave
+    lock: Arc<Mutex<()>>,
+}
+
+impl LocalStore{
+    pub fn new(path: PathBuf) -> Self {
+        LocalStore{
+            path,
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub fn default_path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("gildaga")
+            .join("scores.ron")
+    }
+
+    fn read(path: &PathBuf) -> Result<Vec<StoredScore>, String> {
+        if !path.exists(){
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        ron::de::from_str(&content).map_err(|e| e.to_string())
+    }
+
+    fn write(path: &PathBuf, rows: &Vec<StoredScore>) -> Result<(), String> {
+        if let Some(dir) = path.parent(){
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let content = ron::ser::to_string_pretty(rows, Default::default()).map_err(|e| e.to_string())?;
+        fs::write(path, content).map_err(|e| e.to_string())
+    }
+}
+
+impl ScoreStore for LocalStore{
+    fn migrate(&self) -> BoxFuture<'static, Result<(), String>> {
+        //missing fields of older files are filled in by serde
+        async { Ok(()) }.boxed()
+    }
+
+    fn save(&self, score: NewScore) -> BoxFuture<'static, Result<u64, String>> {
+        let path = self.path.clone();
+        let lock = self.lock.clone();
+        async move {
+            let _guard = lock.lock().map_err(|e| e.to_string())?;
+            let mut rows = LocalStore::read(&path)?;
+            if rows.iter().any(|row| row.run_id.as_ref() == Some(&score.run_id)){
+                return Err(ALREADY_SAVED.to_string());
+            }
+            let id = rows.iter().map(|row| row.id).max().unwrap_or(0) + 1;
+            let created_at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            rows.push(StoredScore{
+                id,
+                username: score.username,
+                score: score.score,
+                created_at,
+                game_mode: score.game_mode,
+                wave: score.wave,
+                game_version: GAME_VERSION.to_string(),
+                run_id: Some(score.run_id),
+                stats: Some(score.stats),
+            });
+            LocalStore::write(&path, &rows)?;
+            Ok(id)
+        }.boxed()
+    }
+
+    fn load_top(&self, limit: u32) -> BoxFuture<'static, Result<Vec<ScoreRow>, String>> {
+        let path = self.path.clone();
+        let lock = self.lock.clone();
+        async move {
+            let _guard = lock.lock().map_err(|e| e.to_string())?;
+            let mut rows = LocalStore::read(&path)?;
+            rows.sort_by(|a, b| b.score.cmp(&a.score).then(a.id.cmp(&b.id)));
+            rows.truncate(limit as usize);
+            Ok(rows.into_iter().map(|row| ScoreRow{
+                id: row.id,
+                username: row.username,
+                score: row.score,
+            }).collect())
+        }.boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use futures::executor::block_on;
+
+    fn new_score(username: &str, score: u32, run_id: &str) -> NewScore {
+        NewScore{
+            username: username.to_string(),
+            score,
+            game_mode: "single".to_string(),
+            wave: 1,
+            run_id: run_id.to_string(),
+            stats: RunStats::default(),
+        }
+    }
+
+    #[test]
+    fn local_store_refuses_a_run_saved_twice(){
+        let path = std::env::temp_dir()
+            .join(format!("gildaga-test-{:016x}", rand::random::<u64>()))
+            .join("scores.ron");
+        let store = LocalStore::new(path.clone());
+
+        assert_eq!(block_on(store.save(new_score("ada", 100, "run-1"))), Ok(1));
+        assert_eq!(block_on(store.save(new_score("ada", 200, "run-1"))), Err(ALREADY_SAVED.to_string()));
+        assert_eq!(block_on(store.save(new_score("bob", 300, "run-2"))), Ok(2));
+
+        let top: Vec<(u64, u32)> = block_on(store.load_top(10)).unwrap().iter().map(|row| (row.id, row.score)).collect();
+        assert_eq!(top, vec![(2, 300), (1, 100)]);
+
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+}
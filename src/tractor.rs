@@ -0,0 +1,214 @@
+use bevy::prelude::*;
+use bevy::sprite::collide_aabb::collide;
+use rand::{thread_rng, Rng};
+use crate::{WinSize, Materials, Enemy, FromEnemy, Player, PlayerState, GameState, PauseState, Slot, HoldsCapturedShip, Diving, Invulnerable, Shield, SCALE};
+use crate::level::EnemyType;
+use crate::path::FollowPath;
+use crate::state::{kill_player, COLLISIONS};
+
+pub struct TractorPlugin;
+
+//takes the player ship, like the collision systems
+pub const TRACTOR_BEAM: &str = "tractor_beam";
+
+impl Plugin for TractorPlugin{
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(TractorTimer(Timer::from_seconds(BEAM_INTERVAL, true)))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(tractor_attack.system().before(COLLISIONS))
+                    .with_system(tractor_beam.system().label(TRACTOR_BEAM).before(COLLISIONS))
+                    .with_system(captured_ship_follow.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver)
+                    .with_system(tractor_reset.system())
+            );
+    }
+}
+
+//seconds between two tractor beam attacks
+const BEAM_INTERVAL: f32 = 8.;
+//seconds the beam stays on
+const BEAM_TIME: f32 = 3.;
+const BEAM_WIDTH: f32 = 70.;
+//how far above the bottom of the screen the boss hovers while beaming
+const BEAM_HOVER: f32 = 260.;
+
+struct TractorTimer(Timer);
+
+//a boss on its way down to beam or beaming, the grid doesn't move it meanwhile
+pub struct Beaming{
+    timer: Timer,
+    beam: Option<Entity>,
+}
+
+struct TractorBeam;
+
+//the player ship a boss took, it flies on top of the boss
+struct CapturedShip{
+    boss: Entity,
+}
+
+//sends a boss settled in the grid down to beam, one beam at a time
+fn tractor_attack(
+    mut commands: Commands,
+    time: Res<Time>,
+    win_size: Res<WinSize>,
+    player_state: Res<PlayerState>,
+    mut tractor_timer: ResMut<TractorTimer>,
+    beaming_query: Query<&Beaming>,
+    boss_query: Query<(Entity, &Transform, &EnemyType, &PauseState), (With<Enemy>, With<Slot>, Without<FollowPath>, Without<HoldsCapturedShip>)>,
+){
+    tractor_timer.0.tick(time.delta());
+    //a dual fighter has nothing left to lose to the beam
+    if !tractor_timer.0.just_finished() || !player_state.on || player_state.dual || beaming_query.iter().next().is_some() {
+        return;
+    }
+
+    let bosses: Vec<(Entity, Vec2)> = boss_query.iter()
+        .filter(|(_, _, enemy_type, pause)| enemy_type.tractor_beam && !pause.0)
+        .map(|(entity, tf, _, _)| (entity, Vec2::new(tf.translation.x, tf.translation.y)))
+        .collect();
+    if bosses.is_empty() {
+        return;
+    }
+
+    let (entity, start) = bosses[thread_rng().gen_range(0..bosses.len())];
+    let hover = Vec2::new(start.x, -win_size.h / 2. + BEAM_HOVER);
+    commands.entity(entity)
+        .insert(FollowPath::new(vec![start, (start + hover) / 2. + Vec2::new(30., 0.), hover]))
+        .insert(Beaming{
+            timer: Timer::from_seconds(BEAM_TIME, false),
+            beam: None,
+        })
+        .insert(Diving);
+}
+
+fn tractor_beam(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut player_state: ResMut<PlayerState>,
+    ship_materials: Res<Materials>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    time: Res<Time>,
+    win_size: Res<WinSize>,
+    mut boss_query: Query<(Entity, &Transform, &PauseState, &FollowPath, &mut Beaming)>,
+    beam_query: Query<(Entity, &Transform, &Sprite), With<TractorBeam>>,
+    //a shield or respawn grace keeps the beam off
+    player_query: Query<(Entity, &Transform, &Sprite), (With<Player>, Without<Invulnerable>, Without<Shield>)>,
+){
+    //the boss was shot down while beaming
+    let beams: Vec<Entity> = boss_query.iter().filter_map(|(_, _, _, _, beaming)| beaming.beam).collect();
+    for (beam_entity, _, _) in beam_query.iter(){
+        if !beams.contains(&beam_entity) {
+            commands.entity(beam_entity).despawn();
+        }
+    }
+
+    for (boss_entity, boss_tf, pause, path, mut beaming) in boss_query.iter_mut(){
+        if pause.0 || !path.finished() {
+            continue;
+        }
+
+        let beam_entity = match beaming.beam{
+            Some(beam_entity) => beam_entity,
+            None => {
+                //from just below the boss down to the bottom of the screen
+                let top = boss_tf.translation.y - 20.;
+                let bottom = -win_size.h / 2.;
+                let beam_entity = commands
+                    .spawn_bundle(SpriteBundle{
+                        material: materials.add(Color::rgba(0.4, 0.6, 1.0, 0.35).into()),
+                        sprite: Sprite::new(Vec2::new(BEAM_WIDTH, top - bottom)),
+                        transform: Transform::from_xyz(boss_tf.translation.x, (top + bottom) / 2., 5.),
+                        ..Default::default()
+                    })
+                    .insert(TractorBeam)
+                    .insert(FromEnemy)
+                    .id();
+                beaming.beam = Some(beam_entity);
+                beam_entity
+            }
+        };
+
+        let mut captured = false;
+        if player_state.on {
+            if let (Ok((_, beam_tf, beam_sprite)), Ok((player_entity, player_tf, player_sprite))) = (beam_query.get(beam_entity), player_query.single()) {
+                let collision = collide(
+                    beam_tf.translation,
+                    beam_sprite.size,
+                    player_tf.translation,
+                    player_sprite.size * Vec2::from(player_tf.scale.abs()),
+                );
+                if collision.is_some() {
+                    kill_player(&mut commands, &mut player_state, &asset_server, &audio, time.seconds_since_startup(), player_entity, player_tf);
+
+                    //the boss keeps the ship until it is shot down
+                    let ship = commands
+                        .spawn_bundle(SpriteBundle{
+                            material: ship_materials.player.clone(),
+                            transform: Transform{
+                                translation: boss_tf.translation,
+                                scale: Vec3::new(SCALE, -SCALE, 1.),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        })
+                        .insert(CapturedShip{ boss: boss_entity })
+                        .insert(FromEnemy)
+                        .id();
+                    commands.entity(boss_entity).insert(HoldsCapturedShip(ship));
+                    captured = true;
+                }
+            }
+        }
+
+        beaming.timer.tick(time.delta());
+        if captured || beaming.timer.finished() {
+            //back to the grid
+            commands.entity(beam_entity).despawn();
+            commands.entity(boss_entity)
+                .remove::<Beaming>()
+                .remove::<FollowPath>()
+                .remove::<Diving>();
+        }
+    }
+}
+
+fn captured_ship_follow(
+    mut commands: Commands,
+    mut query: QuerySet<(
+        Query<&Transform, With<HoldsCapturedShip>>,
+        Query<(Entity, &mut Transform, &CapturedShip)>,
+    )>,
+){
+    let bosses: Vec<(Entity, Vec3)> = query.q1().iter()
+        .map(|(_, _, ship)| ship.boss)
+        .filter_map(|boss| query.q0().get(boss).ok().map(|tf| (boss, tf.translation)))
+        .collect();
+
+    for (ship_entity, mut tf, ship) in query.q1_mut().iter_mut(){
+        match bosses.iter().find(|(boss, _)| *boss == ship.boss){
+            Some((_, boss_translation)) => {
+                tf.translation.x = boss_translation.x;
+                tf.translation.y = boss_translation.y + 40.;
+            }
+            //the boss is gone without rescuing it
+            None => commands.entity(ship_entity).despawn(),
+        }
+    }
+}
+
+fn tractor_reset(
+    mut commands: Commands,
+    mut tractor_timer: ResMut<TractorTimer>,
+    query: Query<Entity, Or<(With<TractorBeam>, With<CapturedShip>)>>,
+){
+    for entity in query.iter(){
+        commands.entity(entity).despawn();
+    }
+    tractor_timer.0.reset();
+}
@@ -0,0 +1,286 @@
+use bevy::prelude::*;
+use std::mem;
+use crate::{PlayerState, GameState, Wave, WaveBanner, ActiveEnemies, Enemy, FromEnemy, FromPlayer, PowerUpToSpawn};
+use crate::level::{CurrentLevel, Level};
+use crate::powerup::{ActivePowerUps, PowerUp};
+use crate::scoring::Scoring;
+use crate::stats::RunStats;
+use crate::state::COLLISIONS;
+
+pub struct TurnsPlugin;
+
+impl Plugin for TurnsPlugin{
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(Turns::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(turns_start.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    //clears the field after the systems that insert on its entities, see COLLISIONS
+                    .with_system(turn_over.system().after(COLLISIONS))
+            );
+    }
+}
+
+//seconds after a death before the other player takes over, the explosion plays meanwhile
+const TURN_CHANGE_DELAY: f64 = 1.;
+
+//everything of a player that isn't flying right now, the resources hold the one that is
+struct Turn{
+    player: usize,
+    player_state: PlayerState,
+    //reached, a wave left half way starts over on the next turn
+    wave: u32,
+    scoring: Scoring,
+    stats: RunStats,
+    power_ups: ActivePowerUps,
+}
+
+impl Turn{
+    fn new(player: usize) -> Self {
+        Turn{
+            player,
+            player_state: PlayerState::default(),
+            wave: 0,
+            scoring: Scoring::default(),
+            stats: RunStats::default(),
+            power_ups: ActivePowerUps::default(),
+        }
+    }
+}
+
+//the players of the arcade style alternating mode, they take turns on every lost life
+pub struct Turns{
+    //1 or 2, picked in the main menu and kept for the next games
+    pub players: usize,
+    //the one flying, from 0
+    current: usize,
+    //the other player until their last life is gone
+    waiting: Option<Turn>,
+    //a player without lives left while the other one plays on, after the game over the one
+    //whose name entry comes second
+    finished: Option<Turn>,
+    //a player done with the name entry
+    entered: Option<Turn>,
+}
+
+impl Default for Turns{
+    fn default() -> Self {
+        Turns{
+            players: 1,
+            current: 0,
+            waiting: None,
+            finished: None,
+            entered: None,
+        }
+    }
+}
+
+impl Turns{
+    pub fn two_players(&self) -> bool {
+        self.players > 1
+    }
+
+    //in front of the score in the hud
+    pub fn score_label(&self) -> String {
+        if self.two_players() { format!("{}UP ", self.current + 1) } else { "SCORE ".to_string() }
+    }
+
+    //player number, score and wave reached of every player, player 1 first
+    pub fn results(&self, player_state: &PlayerState, wave: &Wave) -> Vec<(usize, u32, u32)> {
+        let mut results = vec![(self.current + 1, player_state.score, wave.number)];
+        for turn in self.waiting.iter().chain(self.finished.iter()).chain(self.entered.iter()){
+            results.push((turn.player + 1, turn.player_state.score, turn.wave));
+        }
+        results.sort_by_key(|(player, _, _)| *player);
+        results
+    }
+
+    //a player still has to enter a name and save the score
+    pub fn entry_pending(&self) -> bool {
+        self.finished.is_some()
+    }
+
+    //puts the player still waiting for the name entry in the resources
+    pub fn next_entry(
+        &mut self,
+        player_state: &mut PlayerState,
+        wave: &mut Wave,
+        scoring: &mut Scoring,
+        stats: &mut RunStats,
+        power_ups: &mut ActivePowerUps,
+    ) -> bool {
+        match self.finished.take(){
+            Some(mut next) => {
+                self.swap(&mut next, player_state, wave, scoring, stats, power_ups);
+                self.entered = Some(next);
+                true
+            }
+            None => false,
+        }
+    }
+
+    //puts the turn in the resources and what they held in the turn
+    fn swap(
+        &mut self,
+        turn: &mut Turn,
+        player_state: &mut PlayerState,
+        wave: &mut Wave,
+        scoring: &mut Scoring,
+        stats: &mut RunStats,
+        power_ups: &mut ActivePowerUps,
+    ){
+        mem::swap(&mut self.current, &mut turn.player);
+        mem::swap(&mut turn.player_state, player_state);
+        mem::swap(&mut turn.wave, &mut wave.number);
+        mem::swap(&mut turn.scoring, scoring);
+        mem::swap(&mut turn.stats, stats);
+        mem::swap(&mut turn.power_ups, power_ups);
+    }
+}
+
+fn turns_start(
+    mut turns: ResMut<Turns>,
+){
+    turns.current = 0;
+    turns.waiting = if turns.two_players() { Some(Turn::new(1)) } else { None };
+    turns.finished = None;
+    turns.entered = None;
+}
+
+//after a death the other player takes over, the game is over once nobody has a life left
+fn turn_over(
+    mut commands: Commands,
+    mut handled: Local<f64>,
+    time: Res<Time>,
+    mut game_state: ResMut<State<GameState>>,
+    mut turns: ResMut<Turns>,
+    mut player_state: ResMut<PlayerState>,
+    mut wave: ResMut<Wave>,
+    mut scoring: ResMut<Scoring>,
+    mut stats: ResMut<RunStats>,
+    mut power_ups: ResMut<ActivePowerUps>,
+    mut active_enemies: ResMut<ActiveEnemies>,
+    asset_server: Res<AssetServer>,
+    levels: Res<Assets<Level>>,
+    current_level: Res<CurrentLevel>,
+    //enemies, lasers of both sides and pickups
+    field_query: Query<Entity, Or<(With<Enemy>, With<FromEnemy>, With<FromPlayer>, With<PowerUp>, With<PowerUpToSpawn>)>>,
+    mut banner_query: Query<&mut Text, With<WaveBanner>>,
+){
+    //alive, not spawned yet or this death is taken care of
+    if player_state.on || player_state.last_shot == 0. || *handled == player_state.last_shot {
+        return;
+    }
+
+    let mut next = match turns.waiting.take(){
+        Some(next) => next,
+        None => {
+            //a pause queued this frame goes first, the game over is tried again after it
+            if player_state.game_over && game_state.set(GameState::GameOver).is_ok() {
+                *handled = player_state.last_shot;
+                //the better of the two enters a name first, see Turns::next_entry for the other one
+                if let Some(mut finished) = turns.finished.take() {
+                    if finished.player_state.score > player_state.score {
+                        turns.swap(&mut finished, &mut *player_state, &mut *wave, &mut *scoring, &mut *stats, &mut *power_ups);
+                    }
+                    turns.finished = Some(finished);
+                }
+            }
+            return;
+        }
+    };
+    //kills of the last moment still count for the player who made them
+    if time.seconds_since_startup() < player_state.last_shot + TURN_CHANGE_DELAY {
+        turns.waiting = Some(next);
+        return;
+    }
+
+    let game_over = player_state.game_over;
+    turns.swap(&mut next, &mut *player_state, &mut *wave, &mut *scoring, &mut *stats, &mut *power_ups);
+    if game_over {
+        turns.finished = Some(next);
+    } else {
+        turns.waiting = Some(next);
+    }
+
+    //the field is cleared for the next player, who gets the respawn delay as well
+    for entity in field_query.iter(){
+        commands.entity(entity).despawn_recursive();
+    }
+    active_enemies.0 = 0;
+    player_state.last_shot = time.seconds_since_startup();
+    *handled = player_state.last_shot;
+
+    let number = wave.number.max(1);
+    wave.number = number;
+    wave.to_spawn = current_level.get(&levels, &asset_server)
+        .map(|level| level.wave(number).spawn_queue())
+        .unwrap_or_default();
+    wave.banner.reset();
+    for mut text in banner_query.iter_mut(){
+        text.sections[0].value = format!("Player {}\nWave {}", turns.current + 1, wave.number);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn turn(player: usize, score: u32, wave: u32) -> Turn {
+        let mut turn = Turn::new(player);
+        turn.player_state.score = score;
+        turn.wave = wave;
+        turn
+    }
+
+    fn flying(score: u32, wave: u32) -> (PlayerState, Wave) {
+        let player_state = PlayerState{ score, ..PlayerState::default() };
+        let wave = Wave{ number: wave, ..Wave::default() };
+        (player_state, wave)
+    }
+
+    #[test]
+    fn swap_exchanges_the_players(){
+        let mut turns = Turns{ players: 2, ..Turns::default() };
+        let mut other = turn(1, 300, 4);
+        let (mut player_state, mut wave) = flying(100, 2);
+        let (mut scoring, mut stats, mut power_ups) = (Scoring::default(), RunStats::default(), ActivePowerUps::default());
+        stats.hits = 7;
+
+        turns.swap(&mut other, &mut player_state, &mut wave, &mut scoring, &mut stats, &mut power_ups);
+        assert_eq!(turns.current, 1);
+        assert_eq!((player_state.score, wave.number, stats.hits), (300, 4, 0));
+        assert_eq!((other.player, other.player_state.score, other.wave, other.stats.hits), (0, 100, 2, 7));
+    }
+
+    #[test]
+    fn results_player_one_first(){
+        let turns = Turns{ players: 2, current: 1, waiting: Some(turn(0, 100, 3)), ..Turns::default() };
+        let (player_state, wave) = flying(300, 2);
+        assert_eq!(turns.results(&player_state, &wave), vec![(1, 100, 3), (2, 300, 2)]);
+
+        let turns = Turns::default();
+        assert_eq!(turns.results(&player_state, &wave), vec![(1, 300, 2)]);
+    }
+
+    #[test]
+    fn next_entry_once_per_player(){
+        let mut turns = Turns{ players: 2, finished: Some(turn(1, 50, 1)), ..Turns::default() };
+        let (mut player_state, mut wave) = flying(200, 3);
+        let (mut scoring, mut stats, mut power_ups) = (Scoring::default(), RunStats::default(), ActivePowerUps::default());
+        assert!(turns.entry_pending());
+
+        assert!(turns.next_entry(&mut player_state, &mut wave, &mut scoring, &mut stats, &mut power_ups));
+        assert_eq!((turns.current, player_state.score, wave.number), (1, 50, 1));
+        assert!(!turns.entry_pending());
+        //both still show up in the results
+        assert_eq!(turns.results(&player_state, &wave), vec![(1, 200, 3), (2, 50, 1)]);
+
+        assert!(!turns.next_entry(&mut player_state, &mut wave, &mut scoring, &mut stats, &mut power_ups));
+        assert_eq!(player_state.score, 50);
+    }
+}
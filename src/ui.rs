@@ -1,40 +1,479 @@
 use bevy::prelude::*;
 use bevy_egui::{EguiContext, egui};
-use crate::{PlayerState, GameState, Materials, ButtonSaveToDB, ButtonSaveToDBLabel};
-use futures::executor::block_on;
-use sqlx::mysql::MySqlPoolOptions;
+use crate::{PlayerState, GameState, Materials, ButtonSaveToDB, ButtonSaveToDBLabel, GAMEOVER_SFX, LastSavedScore, NAME_PLACEHOLDER, Wave};
+use std::fmt;
+use serde::Deserialize;
+use crate::config::Config;
+use crate::database::{SaveScoreRequest, ScoreSaved};
+use crate::store::NewScore;
+use crate::stats::RunStats;
+use crate::turns::Turns;
+use crate::scoring::Scoring;
+use crate::powerup::ActivePowerUps;
 
 pub struct UiPlugin;
 
 impl Plugin for UiPlugin{
     fn build(&self, app: &mut AppBuilder) {
+        let name_entry = app.world().get_resource::<Config>().expect("Config has to be inserted before the UiPlugin").name_entry;
+
         app
-            .add_system(ui_text_box.system())
-            .add_system(button_system.system());
+            .insert_resource(name_entry)
+            .insert_resource(SaveStatus::Idle)
+            .insert_resource(Initials::default())
+            .add_event::<NextNameEntry>()
+            .add_system(save_status_system.system())
+            .add_system_set(
+                SystemSet::on_enter(GameState::HighScoreEntry)
+                    .with_system(highscore_entry_enter.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::HighScoreEntry)
+                    .with_system(ui_text_box.system())
+                    .with_system(button_system.system())
+                    .with_system(new_game_button.system())
+                    .with_system(initials_system.system())
+                    .with_system(next_name_entry.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::HighScoreEntry)
+                    .with_system(highscore_entry_exit.system())
+            );
+    }
+}
+
+//how the name is entered after game over, picked in the options menu
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NameEntryMode{
+    Text,
+    Initials,
+}
+
+impl NameEntryMode{
+    pub fn label(&self) -> &'static str {
+        match self{
+            NameEntryMode::Text => "Name entry: Text",
+            NameEntryMode::Initials => "Name entry: Initials",
+        }
+    }
+
+    pub fn toggled(&self) -> Self {
+        match self{
+            NameEntryMode::Text => NameEntryMode::Initials,
+            NameEntryMode::Initials => NameEntryMode::Text,
+        }
+    }
+}
+
+enum SaveStatus{
+    Idle,
+    Saving,
+    Saved,
+    Failed(String),
+}
+
+impl SaveStatus{
+    fn label(&self) -> String {
+        match self{
+            SaveStatus::Idle => String::new(),
+            SaveStatus::Saving => "saving...".to_string(),
+            SaveStatus::Saved => "score saved!".to_string(),
+            SaveStatus::Failed(error) => format!("could not save: {}", error),
+        }
+    }
+}
+
+const INITIALS_CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789.";
+const INITIALS_LEN: usize = 3;
+
+//arcade style name entry, every slot indexes into INITIALS_CHARSET
+#[derive(Default)]
+struct Initials{
+    letters: [usize; INITIALS_LEN],
+    current: usize,
+}
+
+impl Initials{
+    fn name(&self) -> String {
+        self.letters.iter().map(|&i| INITIALS_CHARSET[i] as char).collect()
+    }
+}
+
+struct InitialsEntry;
+
+//the other player of an alternating game enters a name next
+struct NextNameEntry;
+
+//short enough to fit a row of the high score table
+const MAX_NAME_LEN: usize = 16;
+
+enum NameError{
+    Empty,
+    Placeholder,
+    TooLong,
+    InvalidChar(char),
+}
+
+impl fmt::Display for NameError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self{
+            NameError::Empty => write!(f, "enter a name"),
+            NameError::Placeholder => write!(f, "enter your own name"),
+            NameError::TooLong => write!(f, "at most {} characters", MAX_NAME_LEN),
+            NameError::InvalidChar(c) => write!(f, "'{}' is not allowed", c),
+        }
+    }
+}
+
+//returns the trimmed name that can be saved
+fn validate_name(name: &str) -> Result<String, NameError>{
+    let name = name.trim();
+    if name.is_empty(){
+        return Err(NameError::Empty);
+    }
+    if name == NAME_PLACEHOLDER{
+        return Err(NameError::Placeholder);
+    }
+    if name.chars().count() > MAX_NAME_LEN{
+        return Err(NameError::TooLong);
+    }
+    //letters and digits of any script, the score table is utf8mb4
+    if let Some(c) = name.chars().find(|c| !(c.is_alphanumeric() || " -_.'".contains(*c))){
+        return Err(NameError::InvalidChar(c));
+    }
+    Ok(name.to_string())
+}
+
+fn highscore_entry_enter(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    name_entry: Res<NameEntryMode>,
+    mut cmaterials: ResMut<Assets<ColorMaterial>>,
+    mut save_status: ResMut<SaveStatus>,
+    mut initials: ResMut<Initials>,
+    player_state: Res<PlayerState>,
+    mut button_query: Query<&mut Visible, Or<(With<ButtonSaveToDB>, With<ButtonSaveToDBLabel>)>>,
+    mut label_query: Query<&mut Text, With<ButtonSaveToDBLabel>>,
+){
+    *save_status = SaveStatus::Idle;
+    //the label still shows the result of the previous run
+    for mut text in label_query.iter_mut(){
+        text.sections[0].value = "Name:\nScore: ".to_owned() + player_state.score.to_string().as_str() + "\nSave to DB";
+        text.sections[0].style.color = Color::rgb(0.9,0.9,0.9);
+    }
+    let music = asset_server.load(GAMEOVER_SFX);
+    audio.play(music);
+
+    if *name_entry == NameEntryMode::Text{
+        for mut visible in button_query.iter_mut(){
+            visible.is_visible = true;
+        }
+        return;
+    }
+
+    //initials are confirmed with fire, no save button needed
+    *initials = Initials::default();
+    let style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: 60.0,
+        color: Color::WHITE,
+    };
+    let mut sections: Vec<TextSection> = (0..INITIALS_LEN).map(|_| TextSection{
+        value: String::new(),
+        style: style.clone(),
+    }).collect();
+    sections.push(TextSection{
+        value: String::new(),
+        style: TextStyle{ font_size: 25.0, ..style },
+    });
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::FlexStart,
+                ..Default::default()
+            },
+            material: cmaterials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text{
+                    sections,
+                    alignment: TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        ..Default::default()
+                    },
+                },
+                style: Style {
+                    margin: Rect{
+                        bottom: Val::Px(120.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+                .insert(InitialsEntry);
+        });
+}
+
+fn highscore_entry_exit(
+    mut commands: Commands,
+    materials: Res<Materials>,
+    initials_query: Query<&Parent, With<InitialsEntry>>,
+    mut button_query: Query<&mut Visible, Or<(With<ButtonSaveToDB>, With<ButtonSaveToDBLabel>)>>,
+    mut material_query: Query<&mut Handle<ColorMaterial>, With<ButtonSaveToDB>>,
+){
+    for mut visible in button_query.iter_mut(){
+        visible.is_visible = false;
+    }
+    for mut material in material_query.iter_mut(){
+        *material = materials.normal.clone();
+    }
+    for parent in initials_query.iter(){
+        commands.entity(parent.0).despawn_recursive();
     }
 }
 
 fn ui_text_box(
     mut egui_ctx: ResMut<EguiContext>,
     mut player_state: ResMut<PlayerState>,
-    assets: Res<AssetServer>,
-    game_state: Res<GameState>,
+    name_entry: Res<NameEntryMode>,
+    save_status: Res<SaveStatus>,
 ) {
-    if game_state.0 == "gameover" {
-        egui::Area::new("my_area")
-            .fixed_pos(egui::pos2(370.0, 450.0))
-            .show(egui_ctx.ctx(), |ui| {
-                ui.horizontal(|ui| {
-                    ui.text_edit_singleline(&mut player_state.username);
-                });
+    if *name_entry != NameEntryMode::Text{
+        return;
+    }
+    egui::Area::new("my_area")
+        .fixed_pos(egui::pos2(370.0, 450.0))
+        .show(egui_ctx.ctx(), |ui| {
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut player_state.username).hint_text(NAME_PLACEHOLDER));
             });
+            if let Err(error) = validate_name(&player_state.username){
+                ui.colored_label(egui::Color32::from_rgb(230, 80, 80), error.to_string());
+            }
+            if !matches!(*save_status, SaveStatus::Idle){
+                ui.label(save_status.label());
+            }
+        });
+}
+
+fn save_status_system(
+    materials: Res<Materials>,
+    mut save_status: ResMut<SaveStatus>,
+    mut last_saved: ResMut<LastSavedScore>,
+    mut player_state: ResMut<PlayerState>,
+    mut saved: EventReader<ScoreSaved>,
+    mut button_query: Query<(&mut Handle<ColorMaterial>, &Children), With<ButtonSaveToDB>>,
+    mut text_query: Query<&mut Text>,
+){
+    for event in saved.iter(){
+        //a save of an earlier run that finished after a new game started
+        if event.run_id != player_state.run_id{
+            if let Ok(id) = &event.result{
+                last_saved.0 = Some(*id);
+            }
+            continue;
+        }
+        match &event.result{
+            Ok(id) => {
+                last_saved.0 = Some(*id);
+                *save_status = SaveStatus::Saved;
+                player_state.submitted = true;
+
+                //the save button stays pressed, it won't react anymore this run
+                for (mut material, children) in button_query.iter_mut(){
+                    *material = materials.pressed.clone();
+                    if let Ok(mut text) = text_query.get_mut(children[0]){
+                        text.sections[0].value = "Name:\nScore: ".to_owned() + player_state.score.to_string().as_str() + "\nSaved";
+                        text.sections[0].style.color = Color::rgb(0.1,0.9,0.1);
+                    }
+                }
+            }
+            Err(error) => *save_status = SaveStatus::Failed(error.clone()),
+        }
+    }
+}
+
+fn initials_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_input: Res<Input<GamepadButton>>,
+    name_entry: Res<NameEntryMode>,
+    player_state: Res<PlayerState>,
+    wave: Res<Wave>,
+    stats: Res<RunStats>,
+    turns: Res<Turns>,
+    mut initials: ResMut<Initials>,
+    mut save_status: ResMut<SaveStatus>,
+    mut game_state: ResMut<State<GameState>>,
+    mut save_requests: EventWriter<SaveScoreRequest>,
+    mut next_entry: EventWriter<NextNameEntry>,
+    mut text_query: Query<&mut Text, With<InitialsEntry>>,
+){
+    if *name_entry != NameEntryMode::Initials{
+        return;
+    }
+    let key = |codes: &[KeyCode], button: GamepadButtonType| {
+        codes.iter().any(|code| keyboard_input.just_pressed(*code))
+            || gamepad_input.get_just_pressed().any(|pressed| pressed.1 == button)
+    };
+    let up = key(&[KeyCode::Up, KeyCode::W], GamepadButtonType::DPadUp);
+    let down = key(&[KeyCode::Down, KeyCode::S], GamepadButtonType::DPadDown);
+    let confirm = key(&[KeyCode::Space, KeyCode::Return], GamepadButtonType::South);
+    let back = key(&[KeyCode::Back], GamepadButtonType::East);
+
+    let done = initials.current >= INITIALS_LEN;
+    let mut save = false;
+    if done {
+        //once the save went through or was given up on fire goes on to the other player or the next game
+        let mut next = false;
+        if confirm {
+            match *save_status{
+                SaveStatus::Saving => {}
+                //the score isn't lost, fire tries again
+                SaveStatus::Failed(_) => save = true,
+                _ => next = true,
+            }
+        } else if back && matches!(*save_status, SaveStatus::Failed(_)) {
+            //gives up on the save
+            next = true;
+        }
+        if next {
+            if turns.entry_pending() {
+                next_entry.send(NextNameEntry);
+            } else {
+                game_state.set(GameState::Playing).ok();
+            }
+        }
+    } else {
+        let current = initials.current;
+        let count = INITIALS_CHARSET.len();
+        if up {
+            initials.letters[current] = (initials.letters[current] + 1) % count;
+        }
+        if down {
+            initials.letters[current] = (initials.letters[current] + count - 1) % count;
+        }
+        if back && current > 0 {
+            initials.current -= 1;
+        }
+        if confirm {
+            initials.current += 1;
+            save = initials.current == INITIALS_LEN;
+        }
+    }
+    if save {
+        save_requests.send(SaveScoreRequest(new_score(initials.name(), &player_state, &wave, &stats, &turns)));
+        *save_status = SaveStatus::Saving;
+    }
+
+    for mut text in text_query.iter_mut(){
+        for (i, section) in text.sections.iter_mut().take(INITIALS_LEN).enumerate(){
+            section.value = (INITIALS_CHARSET[initials.letters[i]] as char).to_string();
+            section.style.color = if i == initials.current {
+                Color::rgb(0.35, 0.75, 0.35)
+            } else {
+                Color::WHITE
+            };
+        }
+        text.sections[INITIALS_LEN].value = match &*save_status{
+            SaveStatus::Idle => "\nup/down to pick, fire to confirm".to_string(),
+            SaveStatus::Saving => format!("\n{}", save_status.label()),
+            SaveStatus::Failed(_) => format!("\n{}\nfire to try again, back to skip", save_status.label()),
+            _ if turns.entry_pending() => format!("\n{}\nfire for the next player", save_status.label()),
+            _ => format!("\n{}\nfire for a new game", save_status.label()),
+        };
+    }
+}
+
+fn new_score(username: String, player_state: &PlayerState, wave: &Wave, stats: &RunStats, turns: &Turns) -> NewScore{
+    NewScore{
+        username,
+        score: player_state.score,
+        game_mode: if turns.two_players() { "alternating" } else { "single" }.to_string(),
+        wave: wave.number,
+        run_id: player_state.run_id.clone(),
+        stats: stats.clone(),
     }
 }
 
+//swaps in the other player of an alternating game and starts the name entry over for them
+fn next_name_entry(
+    mut events: EventReader<NextNameEntry>,
+    mut turns: ResMut<Turns>,
+    mut player_state: ResMut<PlayerState>,
+    mut wave: ResMut<Wave>,
+    mut scoring: ResMut<Scoring>,
+    mut stats: ResMut<RunStats>,
+    mut power_ups: ResMut<ActivePowerUps>,
+    materials: Res<Materials>,
+    mut save_status: ResMut<SaveStatus>,
+    mut initials: ResMut<Initials>,
+    mut material_query: Query<&mut Handle<ColorMaterial>, With<ButtonSaveToDB>>,
+    mut label_query: Query<&mut Text, With<ButtonSaveToDBLabel>>,
+){
+    //a second click in the same frame doesn't skip a player
+    if events.iter().count() == 0 {
+        return;
+    }
+    if !turns.next_entry(&mut player_state, &mut wave, &mut scoring, &mut stats, &mut power_ups) {
+        return;
+    }
+
+    *save_status = SaveStatus::Idle;
+    *initials = Initials::default();
+    for mut material in material_query.iter_mut(){
+        *material = materials.normal.clone();
+    }
+    for mut text in label_query.iter_mut(){
+        text.sections[0].value = "Name:\nScore: ".to_owned() + player_state.score.to_string().as_str() + "\nSave to DB";
+        text.sections[0].style.color = Color::rgb(0.9,0.9,0.9);
+    }
+}
+
+fn new_game_button(
+    mut egui_ctx: ResMut<EguiContext>,
+    mut game_state: ResMut<State<GameState>>,
+    turns: Res<Turns>,
+    save_status: Res<SaveStatus>,
+    mut next_entry: EventWriter<NextNameEntry>,
+) {
+    egui::Area::new("new_game_area")
+        .fixed_pos(egui::pos2(370.0, 520.0))
+        .show(egui_ctx.ctx(), |ui| {
+            ui.horizontal(|ui| {
+                //the other player of an alternating game still gets to save a score
+                if turns.entry_pending() && !matches!(*save_status, SaveStatus::Saving) && ui.button("Next player").clicked() {
+                    next_entry.send(NextNameEntry);
+                }
+                if ui.button("New game").clicked() {
+                    game_state.set(GameState::Playing).ok();
+                }
+                if ui.button("High scores").clicked() {
+                    game_state.set(GameState::HighScores).ok();
+                }
+                if ui.button("Main menu").clicked() {
+                    game_state.set(GameState::MainMenu).ok();
+                }
+            });
+        });
+}
+
 fn button_system(
     materials: Res<Materials>,
-    game_state: Res<GameState>,
-    mut player_state: ResMut<PlayerState>,
+    player_state: Res<PlayerState>,
+    wave: Res<Wave>,
+    stats: Res<RunStats>,
+    turns: Res<Turns>,
+    mut save_status: ResMut<SaveStatus>,
+    mut save_requests: EventWriter<SaveScoreRequest>,
     mut interaction_query: Query<
         (&Interaction, &mut Handle<ColorMaterial>, &Children),
         (Changed<Interaction>, With<ButtonSaveToDB>),
@@ -43,11 +482,11 @@ fn button_system(
     mut label_query: Query<&mut Visible, With<ButtonSaveToDBLabel>>,
 ){
     for (interaction, mut material, children) in interaction_query.iter_mut() {
+        if player_state.submitted{
+            continue;
+        }
         for (mut visible) in label_query.iter_mut(){
             let mut text = text_query.get_mut(children[0]).unwrap();
-            if game_state.0 == "gameover"{
-                visible.is_visible = true;
-            }
             if visible.is_visible{
                 match *interaction{
                     Interaction::Clicked => {
@@ -55,13 +494,13 @@ fn button_system(
                         *material = materials.pressed.clone();
                         text.sections[0].style.color = Color::rgb(0.1,0.9,0.1);
 
-                        let future = save_to_db(&player_state.username, player_state.score);
-
-                        let result = block_on(future);
-
-                        match result{
-                            Ok(_) => player_state.username = "score saved!".to_string(),
-                            Err(_) => player_state.username = "something went wrong".to_string()
+                        //wait for the running save before sending another one
+                        if !matches!(*save_status, SaveStatus::Saving){
+                            //an invalid name is already explained under the text box
+                            if let Ok(username) = validate_name(&player_state.username){
+                                save_requests.send(SaveScoreRequest(new_score(username, &player_state, &wave, &stats, &turns)));
+                                *save_status = SaveStatus::Saving;
+                            }
                         }
                     }
                     Interaction::Hovered => {
@@ -80,9 +519,26 @@ fn button_system(
     }
 }
 
-async fn save_to_db(username: &str, score: u32) -> Result<(), sqlx::Error>{
-    let pool = MySqlPoolOptions::new().max_connections(5).connect("mysql://localhost/gildaga").await?;
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn validate_name_trims(){
+        assert_eq!(validate_name("  Ada Lovelace ").ok(), Some("Ada Lovelace".to_string()));
+        assert_eq!(validate_name("Jürgen_O'Neil-2.").ok(), Some("Jürgen_O'Neil-2.".to_string()));
+    }
 
-    sqlx::query("INSERT INTO score (Username, Score) VALUES ( ?, ? )").bind(username).bind(score).execute(&pool).await?;
-    Ok(())
-}
\ No newline at end of file
+    #[test]
+    fn validate_name_rejects(){
+        assert!(matches!(validate_name("   "), Err(NameError::Empty)));
+        assert!(matches!(validate_name(NAME_PLACEHOLDER), Err(NameError::Placeholder)));
+        assert!(matches!(validate_name(&"a".repeat(MAX_NAME_LEN + 1)), Err(NameError::TooLong)));
+        assert!(matches!(validate_name("a;drop"), Err(NameError::InvalidChar(';'))));
+    }
+
+    #[test]
+    fn validate_name_counts_chars_not_bytes(){
+        assert!(validate_name(&"ü".repeat(MAX_NAME_LEN)).is_ok());
+    }
+}
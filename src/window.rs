@@ -1,5 +1,5 @@
 use bevy::prelude::*;
-use crate::{Materials, WinSize, PauseText, ButtonSaveToDBLabel, ButtonSaveToDB, Player, Enemy, GameOverText, CheatSheetTimer, GameState, PlayerState};
+use crate::{Materials, WinSize, PauseText, WaveBanner, ButtonSaveToDBLabel, ButtonSaveToDB, Player, Enemy, GameOverText, CheatSheetTimer, GameState, PlayerState};
 use bevy_inspector_egui::plugin::InspectorWindows;
 use bevy_inspector_egui::widgets::{InspectorQuerySingle, InspectorQuery};
 use sqlx::mysql::MySqlPoolOptions;
@@ -73,10 +73,54 @@ fn setup(mut commands: Commands,
                 .insert(PauseText);
         });
 
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::FlexEnd,
+                ..Default::default()
+            },
+            material: cmaterials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                visible: Visible{
+                    is_visible: false,
+                    is_transparent: false,
+                },
+                text: Text::with_section(
+                    "Wave",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 60.0,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        ..Default::default()
+                    },
+                ),
+                style: Style {
+                    align_self: AlignSelf::Center,
+                    // above the pause text
+                    margin: Rect{
+                        bottom: Val::Px(200.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+                .insert(WaveBanner);
+        });
+
     commands
         .spawn_bundle(ButtonBundle {
             visible: Visible{
-                is_visible: true,
+                is_visible: false,
                 is_transparent: false,
             },
             style: Style {